@@ -1,8 +1,9 @@
 use std::{
     env,
-    fs::File,
+    fs::{self, File},
     io::Write,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::Command,
 };
 
 const LGFX_FONTS: [&str; 186] = [
@@ -195,21 +196,152 @@ const LGFX_FONTS: [&str; 186] = [
 ];
 
 const LGFX_C_HEADER_PATH: &str = "lgfx_c/lgfx_c.h";
+const LGFX_C_SOURCE_PATH: &str = "lgfx_c/lgfx_c.cpp";
+// LovyanGFX is used from LOVYANGFX_DIR, or checked out here. The build script never fetches it.
+const LOVYANGFX_DEFAULT_DIR: &str = "lgfx_c/LovyanGFX";
+const LOVYANGFX_REPOSITORY: &str = "https://github.com/lovyan03/LovyanGFX";
+
+// Sources of LovyanGFX for the SDL panel, same as examples_for_PC/CMake_SDL of LovyanGFX. Relative to LovyanGFX/src.
+const LOVYANGFX_C_SOURCE_DIRS: [&str; 3] = [
+    "lgfx/Fonts/efont",
+    "lgfx/Fonts/IPA",
+    "lgfx/utility",
+];
+const LOVYANGFX_CPP_SOURCE_DIRS: [&str; 3] = [
+    "lgfx/v1",
+    "lgfx/v1/misc",
+    "lgfx/v1/platforms/sdl",
+];
+const LOVYANGFX_CPP_SOURCES: [&str; 2] = [
+    "lgfx/v1/panel/Panel_Device.cpp",
+    "lgfx/v1/panel/Panel_FrameBufferBase.cpp",
+];
+
+/// Returns the files with the extension in the directory, sorted to keep the build reproducible.
+fn sources_in(dir: &Path, extension: &str) -> anyhow::Result<Vec<PathBuf>> {
+    let mut sources = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == extension) {
+            sources.push(path);
+        }
+    }
+    sources.sort();
+    Ok(sources)
+}
+
+/// Returns the directory of LovyanGFX from LOVYANGFX_DIR or the default checkout.
+fn lovyangfx_dir() -> anyhow::Result<PathBuf> {
+    println!("cargo:rerun-if-env-changed=LOVYANGFX_DIR");
+    let dir = env::var("LOVYANGFX_DIR").map(PathBuf::from).unwrap_or_else(|_| PathBuf::from(LOVYANGFX_DEFAULT_DIR));
+    if !dir.join("src/LovyanGFX.hpp").exists() {
+        anyhow::bail!(
+            "LovyanGFX is not found in {}. Check out {} into {} (e.g. `git submodule update --init` if it is a submodule), or set LOVYANGFX_DIR to its directory.",
+            dir.display(),
+            LOVYANGFX_REPOSITORY,
+            LOVYANGFX_DEFAULT_DIR
+        );
+    }
+    Ok(dir)
+}
+
+/// Returns the include directory of the clang resource headers, e.g. stddef.h and stdint.h.
+/// Searches `$LIBCLANG_PATH/clang/<version>/include` of the newest version,
+/// and then asks `clang -print-resource-dir`, where clang can be changed by CLANG_PATH.
+fn clang_include_dir() -> Option<PathBuf> {
+    println!("cargo:rerun-if-env-changed=LIBCLANG_PATH");
+    println!("cargo:rerun-if-env-changed=CLANG_PATH");
+    if let Ok(libclang_path) = env::var("LIBCLANG_PATH") {
+        let version_of = |path: &Path| -> Option<Vec<u32>> {
+            let name = path.file_name()?.to_str()?;
+            name.split('.').map(|part| part.parse().ok()).collect()
+        };
+        let newest = fs::read_dir(Path::new(&libclang_path).join("clang"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.join("include").is_dir())
+            .filter_map(|path| version_of(&path).map(|version| (version, path)))
+            .max();
+        if let Some((_, path)) = newest {
+            return Some(path.join("include"));
+        }
+    }
+    let clang = env::var("CLANG_PATH").unwrap_or_else(|_| String::from("clang"));
+    let output = Command::new(&clang).arg("-print-resource-dir").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let resource_dir = String::from_utf8(output.stdout).ok()?;
+    Some(PathBuf::from(resource_dir.trim()).join("include"))
+}
+
+/// Compiles lgfx_c and LovyanGFX with the SDL panel for the host.
+/// On ESP-IDF, they are built as the components by lgfx_c/CMakeLists.txt instead.
+fn build_lgfx_c_for_host() -> anyhow::Result<()> {
+    println!("cargo:rerun-if-changed={}", LGFX_C_SOURCE_PATH);
+    let lovyangfx_src = lovyangfx_dir()?.join("src");
+    println!("cargo:rerun-if-changed={}", lovyangfx_src.display());
+
+    // The headless build opens no window, e.g. to run the tests on CI.
+    let headless = env::var("CARGO_FEATURE_HEADLESS").is_ok();
+
+    let mut cpp = cc::Build::new();
+    cpp.cpp(true)
+        .flag_if_supported("-std=c++17")
+        .include("lgfx_c")
+        .include(&lovyangfx_src)
+        .define("LGFX_SDL", None)
+        .warnings(false)
+        .file(LGFX_C_SOURCE_PATH);
+    if headless {
+        cpp.define("LGFX_HEADLESS", None);
+    }
+    for dir in LOVYANGFX_CPP_SOURCE_DIRS {
+        cpp.files(sources_in(&lovyangfx_src.join(dir), "cpp")?);
+    }
+    for source in LOVYANGFX_CPP_SOURCES {
+        cpp.file(lovyangfx_src.join(source));
+    }
+    cpp.compile("lgfx_c");
+
+    // The fonts are referenced from lgfx_c, thus linked after it.
+    let mut c = cc::Build::new();
+    c.include(&lovyangfx_src).warnings(false);
+    for dir in LOVYANGFX_C_SOURCE_DIRS {
+        c.files(sources_in(&lovyangfx_src.join(dir), "c")?);
+    }
+    c.compile("lovyangfx_c");
+
+    println!("cargo:rustc-link-lib=SDL2");
+    Ok(())
+}
 
 // Necessary because of this issue: https://github.com/rust-lang/cargo/issues/9641
 fn main() -> anyhow::Result<()> {
     // Rebuild if LGFX C binding is changed.
     println!("cargo:rerun-if-changed={}", LGFX_C_HEADER_PATH);
 
-    let libclang_path = env::var("LIBCLANG_PATH").expect("LIBCLANG_PATH must be set");
-    let libclang_include_path: PathBuf = [libclang_path.as_str(), "clang", "14.0.0", "include"].iter().collect();
-    let bindings = bindgen::Builder::default()
+    let mut builder = bindgen::Builder::default()
         .header(LGFX_C_HEADER_PATH)
-        .clang_arg("-nostdinc")
-        .clang_arg(["-I", libclang_include_path.to_str().unwrap()].concat())
+        .use_core()
+        .ctypes_prefix("core::ffi");
+    // Only the freestanding headers of clang are used, so the headers of the target toolchain are not needed.
+    // Otherwise libclang searches its default include paths.
+    match clang_include_dir() {
+        Some(clang_include_path) => {
+            builder = builder
+                .clang_arg("-nostdinc")
+                .clang_arg(format!("-I{}", clang_include_path.display()));
+        }
+        None => println!(
+            "cargo:warning=The clang resource headers are not found. Set LIBCLANG_PATH or CLANG_PATH if the bindings fail to be generated."
+        ),
+    }
+    let bindings = builder
         .parse_callbacks(Box::new(bindgen::CargoCallbacks))
         .generate()
-        .expect("Failed to generate LGFX bindings");
+        .map_err(|_| anyhow::anyhow!("Failed to generate LGFX bindings. libclang is required, see README.md."))?;
     {
         let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());        
         bindings
@@ -234,5 +366,9 @@ fn main() -> anyhow::Result<()> {
         writeln!(&mut file, "#[allow(unused)] #[allow(non_upper_case_globals)] pub static {}: LgfxFont = LgfxFont{{ ptr: unsafe {{ &lgfx_font_raw_defs::{} }} }};", font_name, font_name)?;
     }
 
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("linux") {
+        build_lgfx_c_for_host()?;
+    }
+
     Ok(())
 }
@@ -12,56 +12,506 @@
 #define LGFX_AUTODETECT
 #include <LovyanGFX.hpp>
 #include <stdint.h>
+#include <algorithm>
+#include <deque>
+#include <mutex>
+#include <vector>
+
+using namespace lgfx::v1;
 
 #ifndef LGFX_SDL
-static LGFX GFX;
+
+lgfx_target_t lgfx_c_setup(void) 
+{
+    auto gfx = new LGFX();
+    if( !gfx->init() ) {
+        // No panel is detected.
+        gfx->releaseBus();
+        delete gfx;
+        return nullptr;
+    }
+    return reinterpret_cast<lgfx_target_t>(static_cast<LovyanGFX*>(gfx));
+}
+
+// Device built from the configuration passed from Rust. It owns the bus, panel, light and touch.
+class LGFX_Config : public LGFX_Device
+{
+public:
+    ~LGFX_Config()
+    {
+        delete this->touch_instance;
+        delete this->light_instance;
+        delete this->panel_instance;
+        delete this->bus_instance;
+    }
+    IBus* bus_instance = nullptr;
+    Panel_Device* panel_instance = nullptr;
+    ILight* light_instance = nullptr;
+    ITouch* touch_instance = nullptr;
+};
+
+static Panel_Device* create_panel(const ::panel_config_t* config)
+{
+    Panel_Device* panel = nullptr;
+    switch( config->driver ) {
+    case panel_ili9341: panel = new Panel_ILI9341(); break;
+    case panel_ili9342: panel = new Panel_ILI9342(); break;
+    case panel_ili9488: panel = new Panel_ILI9488(); break;
+    case panel_st7735s: panel = new Panel_ST7735S(); break;
+    case panel_st7789:  panel = new Panel_ST7789(); break;
+    case panel_st7796:  panel = new Panel_ST7796(); break;
+    case panel_gc9a01:  panel = new Panel_GC9A01(); break;
+    case panel_ssd1306: panel = new Panel_SSD1306(); break;
+    case panel_sh110x:  panel = new Panel_SH110x(); break;
+#if SOC_LCD_RGB_SUPPORTED
+    case panel_rgb:     panel = new Panel_RGB(); break;
+#endif
+    default: return nullptr;
+    }
+    auto cfg = panel->config();
+    cfg.pin_cs = config->pin_cs;
+    cfg.pin_rst = config->pin_rst;
+    cfg.pin_busy = config->pin_busy;
+    cfg.memory_width = config->memory_width;
+    cfg.memory_height = config->memory_height;
+    cfg.panel_width = config->panel_width;
+    cfg.panel_height = config->panel_height;
+    cfg.offset_x = config->offset_x;
+    cfg.offset_y = config->offset_y;
+    cfg.offset_rotation = config->offset_rotation;
+    cfg.readable = config->readable;
+    cfg.invert = config->invert;
+    cfg.rgb_order = config->rgb_order;
+    cfg.dlen_16bit = config->dlen_16bit;
+    cfg.bus_shared = config->bus_shared;
+    panel->config(cfg);
+    return panel;
+}
+
+static IBus* create_bus(const ::bus_config_t* config, Panel_Device* panel)
+{
+    switch( config->type ) {
+    case bus_spi: {
+        auto bus = new Bus_SPI();
+        auto cfg = bus->config();
+        cfg.spi_host = static_cast<decltype(cfg.spi_host)>(config->port);
+        cfg.spi_mode = config->spi_mode;
+        cfg.freq_write = config->freq_write;
+        cfg.freq_read = config->freq_read;
+        cfg.spi_3wire = config->spi_3wire;
+        cfg.use_lock = config->use_lock;
+        cfg.dma_channel = static_cast<decltype(cfg.dma_channel)>(config->dma_channel);
+        cfg.pin_sclk = config->pin_sclk;
+        cfg.pin_mosi = config->pin_mosi;
+        cfg.pin_miso = config->pin_miso;
+        cfg.pin_dc = config->pin_dc;
+        bus->config(cfg);
+        return bus;
+    }
+    case bus_i2c: {
+        auto bus = new Bus_I2C();
+        auto cfg = bus->config();
+        cfg.i2c_port = config->port;
+        cfg.freq_write = config->freq_write;
+        cfg.freq_read = config->freq_read;
+        cfg.pin_sda = config->pin_sda;
+        cfg.pin_scl = config->pin_scl;
+        cfg.i2c_addr = config->i2c_addr;
+        bus->config(cfg);
+        return bus;
+    }
+    case bus_parallel8: {
+        auto bus = new Bus_Parallel8();
+        auto cfg = bus->config();
+#if defined ( CONFIG_IDF_TARGET_ESP32 )
+        cfg.i2s_port = static_cast<decltype(cfg.i2s_port)>(config->port);
 #else
-static alignas(LGFX) std::uint8_t gfx_storage[sizeof(LGFX)];
-#define GFX (*reinterpret_cast<LGFX*>(gfx_storage))
+        cfg.port = config->port;
 #endif
+        cfg.freq_write = config->freq_write;
+        cfg.pin_wr = config->pin_wr;
+        cfg.pin_rd = config->pin_rd;
+        cfg.pin_rs = config->pin_rs;
+        cfg.pin_d0 = config->pin_d[0];
+        cfg.pin_d1 = config->pin_d[1];
+        cfg.pin_d2 = config->pin_d[2];
+        cfg.pin_d3 = config->pin_d[3];
+        cfg.pin_d4 = config->pin_d[4];
+        cfg.pin_d5 = config->pin_d[5];
+        cfg.pin_d6 = config->pin_d[6];
+        cfg.pin_d7 = config->pin_d[7];
+        bus->config(cfg);
+        return bus;
+    }
+#if SOC_LCD_RGB_SUPPORTED
+    case bus_rgb: {
+        if( panel == nullptr ) return nullptr;
+        auto bus = new Bus_RGB();
+        auto cfg = bus->config();
+        cfg.panel = static_cast<Panel_RGB*>(panel);
+        cfg.pin_d0 = config->pin_d[0];
+        cfg.pin_d1 = config->pin_d[1];
+        cfg.pin_d2 = config->pin_d[2];
+        cfg.pin_d3 = config->pin_d[3];
+        cfg.pin_d4 = config->pin_d[4];
+        cfg.pin_d5 = config->pin_d[5];
+        cfg.pin_d6 = config->pin_d[6];
+        cfg.pin_d7 = config->pin_d[7];
+        cfg.pin_d8 = config->pin_d[8];
+        cfg.pin_d9 = config->pin_d[9];
+        cfg.pin_d10 = config->pin_d[10];
+        cfg.pin_d11 = config->pin_d[11];
+        cfg.pin_d12 = config->pin_d[12];
+        cfg.pin_d13 = config->pin_d[13];
+        cfg.pin_d14 = config->pin_d[14];
+        cfg.pin_d15 = config->pin_d[15];
+        cfg.pin_henable = config->pin_henable;
+        cfg.pin_vsync = config->pin_vsync;
+        cfg.pin_hsync = config->pin_hsync;
+        cfg.pin_pclk = config->pin_pclk;
+        cfg.freq_write = config->freq_write;
+        cfg.hsync_polarity = config->hsync_polarity;
+        cfg.hsync_front_porch = config->hsync_front_porch;
+        cfg.hsync_pulse_width = config->hsync_pulse_width;
+        cfg.hsync_back_porch = config->hsync_back_porch;
+        cfg.vsync_polarity = config->vsync_polarity;
+        cfg.vsync_front_porch = config->vsync_front_porch;
+        cfg.vsync_pulse_width = config->vsync_pulse_width;
+        cfg.vsync_back_porch = config->vsync_back_porch;
+        cfg.pclk_idle_high = config->pclk_idle_high;
+        cfg.de_idle_high = config->de_idle_high;
+        bus->config(cfg);
+        return bus;
+    }
+#endif
+    default:
+        return nullptr;
+    }
+}
 
-using namespace lgfx::v1;
+static ILight* create_light(const ::light_config_t* config)
+{
+    auto light = new Light_PWM();
+    auto cfg = light->config();
+    cfg.pin_bl = config->pin_bl;
+    cfg.invert = config->invert;
+    cfg.freq = config->freq;
+    cfg.pwm_channel = config->pwm_channel;
+    light->config(cfg);
+    return light;
+}
 
-#ifndef LGFX_SDL
+static ITouch* create_touch(const ::touch_config_t* config)
+{
+    ITouch* touch = nullptr;
+    switch( config->driver ) {
+    case touch_ft5x06:  touch = new Touch_FT5x06(); break;
+    case touch_gt911:   touch = new Touch_GT911(); break;
+    case touch_xpt2046: touch = new Touch_XPT2046(); break;
+    case touch_cst816s: touch = new Touch_CST816S(); break;
+    default: return nullptr;
+    }
+    auto cfg = touch->config();
+    cfg.x_min = config->x_min;
+    cfg.x_max = config->x_max;
+    cfg.y_min = config->y_min;
+    cfg.y_max = config->y_max;
+    cfg.pin_int = config->pin_int;
+    cfg.pin_rst = config->pin_rst;
+    cfg.pin_cs = config->pin_cs;
+    cfg.pin_sclk = config->pin_sclk;
+    cfg.pin_mosi = config->pin_mosi;
+    cfg.pin_miso = config->pin_miso;
+    cfg.pin_sda = config->pin_sda;
+    cfg.pin_scl = config->pin_scl;
+    cfg.offset_rotation = config->offset_rotation;
+    cfg.bus_shared = config->bus_shared;
+    cfg.freq = config->freq;
+    if( config->driver == touch_xpt2046 ) {
+        cfg.spi_host = static_cast<decltype(cfg.spi_host)>(config->port);
+    } else {
+        cfg.i2c_port = config->port;
+        cfg.i2c_addr = config->i2c_addr;
+    }
+    touch->config(cfg);
+    return touch;
+}
 
-lgfx_target_t lgfx_c_setup(void) 
+lgfx_target_t lgfx_c_setup_with_config(const ::bus_config_t *bus, const ::panel_config_t *panel, const ::light_config_t *light, const ::touch_config_t *touch)
 {
-    GFX.init();
-    return reinterpret_cast<lgfx_target_t>(static_cast<LovyanGFX*>(&GFX));
+    if( bus == nullptr || panel == nullptr ) return nullptr;
+    auto gfx = new LGFX_Config();
+    gfx->panel_instance = create_panel(panel);
+    if( gfx->panel_instance != nullptr ) {
+        gfx->bus_instance = create_bus(bus, gfx->panel_instance);
+    }
+    if( gfx->panel_instance == nullptr || gfx->bus_instance == nullptr ) {
+        delete gfx;
+        return nullptr;
+    }
+    gfx->panel_instance->setBus(gfx->bus_instance);
+    if( light != nullptr ) {
+        gfx->light_instance = create_light(light);
+        gfx->panel_instance->setLight(gfx->light_instance);
+    }
+    if( touch != nullptr ) {
+        gfx->touch_instance = create_touch(touch);
+        if( gfx->touch_instance != nullptr ) {
+            gfx->panel_instance->setTouch(gfx->touch_instance);
+        }
+    }
+    gfx->setPanel(gfx->panel_instance);
+    if( !gfx->init() ) {
+        gfx->releaseBus();
+        delete gfx;
+        return nullptr;
+    }
+    return reinterpret_cast<lgfx_target_t>(static_cast<LovyanGFX*>(gfx));
 }
 
 #else 
 
+// Panel_sdl creates the window lazily and does not expose it, so windows are associated with the devices
+// in the order of creation, which is the order the devices were set up.
+struct sdl_window_owner
+{
+    LGFX* gfx;
+    uint32_t window_id;     // 0 until the window is shown.
+};
+static std::vector<sdl_window_owner> sdl_window_owners;
+// Windows of the freed devices. SDL windows must be destroyed on the main thread, which handles the SDL events.
+static std::vector<uint32_t> sdl_windows_to_destroy;
+// Guards the state above and the event queue, since the devices may be freed on any thread.
+static std::mutex sdl_state_mutex;
+
+static void assign_window(uint32_t window_id)
+{
+    for( auto& owner : sdl_window_owners ) {
+        if( owner.window_id == window_id ) return;
+    }
+    for( auto& owner : sdl_window_owners ) {
+        if( owner.window_id == 0 ) {
+            owner.window_id = window_id;
+            return;
+        }
+    }
+}
+
+static lgfx_target_t window_owner(uint32_t window_id)
+{
+    for( auto& owner : sdl_window_owners ) {
+        if( owner.window_id != 0 && owner.window_id == window_id ) {
+            return reinterpret_cast<lgfx_target_t>(static_cast<LovyanGFX*>(owner.gfx));
+        }
+    }
+    return nullptr;
+}
+
+// Events observed by the event watch, which are consumed by lgfx_c_panel_sdl_poll_event.
+// Panel_sdl::sdl_event_handler still receives all events since the event watch does not remove them from the SDL queue.
+static constexpr std::size_t EVENT_QUEUE_SIZE = 32;
+static std::deque<::event_t> event_queue;
+
+static void push_event(::event_type_t type, int32_t keycode, lgfx_target_t target)
+{
+    if( event_queue.size() == EVENT_QUEUE_SIZE ) {
+        // Drop the oldest event.
+        event_queue.pop_front();
+    }
+    ::event_t event;
+    event.type = type;
+    event.keycode = keycode;
+    event.target = target;
+    event_queue.push_back(event);
+}
+
+static int event_watch(void*, SDL_Event* event)
+{
+    std::lock_guard<std::mutex> lock(sdl_state_mutex);
+    switch( event->type ) {
+    case SDL_QUIT:
+        // SDL requests to quit once the last window is closed, after SDL_WINDOWEVENT_CLOSE of the window.
+        for( auto& owner : sdl_window_owners ) {
+            push_event(event_quit, 0, reinterpret_cast<lgfx_target_t>(static_cast<LovyanGFX*>(owner.gfx)));
+        }
+        break;
+    case SDL_WINDOWEVENT:
+        if( event->window.event == SDL_WINDOWEVENT_SHOWN ) {
+            assign_window(event->window.windowID);
+        }
+        else if( event->window.event == SDL_WINDOWEVENT_CLOSE ) {
+            push_event(event_close, 0, window_owner(event->window.windowID));
+        }
+        break;
+    case SDL_KEYDOWN:
+        if( event->key.repeat == 0 ) {
+            push_event(event_key_down, event->key.keysym.sym, window_owner(event->key.windowID));
+        }
+        break;
+    case SDL_KEYUP:
+        push_event(event_key_up, event->key.keysym.sym, window_owner(event->key.windowID));
+        break;
+    default:
+        break;
+    }
+    return 1;
+}
+
 lgfx_target_t lgfx_c_setup_with_size(int width, int height) 
 {
-    memset(gfx_storage, 0, sizeof(LGFX));
-    new(gfx_storage) LGFX(width, height);
-    GFX.init();
-    return reinterpret_cast<lgfx_target_t>(static_cast<LovyanGFX*>(&GFX));
+    static std::once_flag event_watch_added;
+#ifdef LGFX_HEADLESS
+    // Must be set before SDL initializes the video subsystem. Does not override the driver chosen by the user.
+    SDL_setenv("SDL_VIDEODRIVER", "dummy", 0);
+#endif
+    auto gfx = new LGFX(width, height);
+    if( !gfx->init() ) {
+        delete gfx;
+        return nullptr;
+    }
+    {
+        std::lock_guard<std::mutex> lock(sdl_state_mutex);
+        sdl_window_owners.push_back({gfx, 0});
+    }
+    // Events are shared among all windows. Not added under sdl_state_mutex, since SDL calls event_watch,
+    // which takes the mutex, while holding its lock of the event watches.
+    std::call_once(event_watch_added, [] { SDL_AddEventWatch(event_watch, nullptr); });
+    return reinterpret_cast<lgfx_target_t>(static_cast<LovyanGFX*>(gfx));
 }
 
 void lgfx_c_panel_sdl_event_handler(void)
 {
+    std::vector<uint32_t> windows;
+    {
+        std::lock_guard<std::mutex> lock(sdl_state_mutex);
+        windows.swap(sdl_windows_to_destroy);
+    }
+    // Destroyed without the lock since destroying a window emits events to the event watch.
+    // The window may have been destroyed by Panel_sdl already, in which case SDL_GetWindowFromID returns null.
+    for( auto window_id : windows ) {
+        auto window = SDL_GetWindowFromID(window_id);
+        if( window != nullptr ) {
+            SDL_DestroyWindow(window);
+        }
+    }
     lgfx::Panel_sdl::sdl_event_handler();
 }
+
+lgfx_target_t lgfx_c_setup_with_config(const ::bus_config_t *bus, const ::panel_config_t *panel, const ::light_config_t*, const ::touch_config_t*)
+{
+    // The simulator ignores the hardware configuration and only simulates the panel size.
+    if( bus == nullptr || panel == nullptr ) return nullptr;
+    return lgfx_c_setup_with_size(panel->panel_width, panel->panel_height);
+}
+
+bool lgfx_c_panel_sdl_poll_event(lgfx_target_t target, ::event_t *event)
+{
+    std::lock_guard<std::mutex> lock(sdl_state_mutex);
+    // Events of the windows not associated with any device go to the first display polling them.
+    auto it = std::find_if(event_queue.begin(), event_queue.end(), [target](const ::event_t& event) {
+        return event.target == target || event.target == nullptr;
+    });
+    if( it == event_queue.end() ) return false;
+    *event = *it;
+    event_queue.erase(it);
+    return true;
+}
+#endif
+
+void lgfx_c_free(lgfx_target_t target)
+{
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->waitDisplay();
+#ifndef LGFX_SDL
+    gfx->releaseBus();
+    delete gfx;
+#else
+    {
+        std::lock_guard<std::mutex> lock(sdl_state_mutex);
+        for( auto it = sdl_window_owners.begin(); it != sdl_window_owners.end(); ++it ) {
+            if( static_cast<LGFX_Device*>(it->gfx) == gfx ) {
+                if( it->window_id != 0 ) {
+                    sdl_windows_to_destroy.push_back(it->window_id);
+                }
+                sdl_window_owners.erase(it);
+                break;
+            }
+        }
+        // Nobody polls the events of the display anymore.
+        event_queue.erase(std::remove_if(event_queue.begin(), event_queue.end(), [target](const ::event_t& event) {
+            return event.target == target;
+        }), event_queue.end());
+    }
+    // Deleting the device removes the panel from the update list of Panel_sdl.
+    // The window is destroyed by the next lgfx_c_panel_sdl_event_handler on the main thread.
+    delete gfx;
 #endif
+}
 
 ::epd_mode_t lgfx_c_get_epd_mode(lgfx_target_t target) {
-    auto gfx = static_cast<LGFX*>(reinterpret_cast<LovyanGFX*>(target));
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
     return static_cast<::epd_mode_t>(gfx->getEpdMode());
 }
 void lgfx_c_set_epd_mode(lgfx_target_t target, ::epd_mode_t epd_mode) {
-    auto gfx = static_cast<LGFX*>(reinterpret_cast<LovyanGFX*>(target));
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
     gfx->setEpdMode(static_cast<lgfx::v1::epd_mode_t>(epd_mode));
 }
 bool lgfx_c_is_epd(lgfx_target_t target) {
-    auto gfx = static_cast<LGFX*>(reinterpret_cast<LovyanGFX*>(target));
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
     return gfx->isEPD();
 }
+void lgfx_c_display(lgfx_target_t target) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->display();
+}
+void lgfx_c_display_region(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->display(x, y, w, h);
+}
+void lgfx_c_wait_display(lgfx_target_t target) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->waitDisplay();
+}
+bool lgfx_c_display_busy(lgfx_target_t target) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    return gfx->displayBusy();
+}
+void lgfx_c_set_auto_display(lgfx_target_t target, bool enable) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->setAutoDisplay(enable);
+}
 void lgfx_c_set_rotation(lgfx_target_t target, uint_fast8_t rotation) {
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     gfx->setRotation(rotation);
 }
+uint8_t lgfx_c_get_rotation(lgfx_target_t target) {
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    return gfx->getRotation();
+}
+void lgfx_c_set_brightness(lgfx_target_t target, uint8_t brightness) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->setBrightness(brightness);
+}
+uint8_t lgfx_c_get_brightness(lgfx_target_t target) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    return gfx->getBrightness();
+}
+void lgfx_c_sleep(lgfx_target_t target) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->sleep();
+}
+void lgfx_c_wakeup(lgfx_target_t target) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->wakeup();
+}
+void lgfx_c_power_save(lgfx_target_t target, bool enable) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->powerSave(enable);
+}
+void lgfx_c_invert_display(lgfx_target_t target, bool invert) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->invertDisplay(invert);
+}
 
 int32_t lgfx_c_width(lgfx_target_t target) {
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
@@ -90,6 +540,10 @@ void lgfx_c_clear_rgb332(lgfx_target_t target, uint8_t color) {
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     gfx->clear(color);
 }
+void lgfx_c_clear_rgb565(lgfx_target_t target, uint16_t color) {
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    gfx->clear(color);
+}
 void lgfx_c_clear_rgb888(lgfx_target_t target, uint32_t color) {
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     gfx->clear(color);
@@ -99,6 +553,10 @@ void lgfx_c_fill_rect_rgb332(lgfx_target_t target, int32_t left, int32_t top, in
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     gfx->fillRect(left, top, width, height, rgb332_t(color));
 }
+void lgfx_c_fill_rect_rgb565(lgfx_target_t target, int32_t left, int32_t top, int32_t width, int32_t height, uint16_t color) {
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    gfx->fillRect(left, top, width, height, rgb565_t(color));
+}
 void lgfx_c_fill_rect_rgb888(lgfx_target_t target, int32_t left, int32_t top, int32_t width, int32_t height, uint32_t color) {
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     gfx->fillRect(left, top, width, height, rgb888_t(color));
@@ -108,6 +566,10 @@ void lgfx_c_draw_line_rgb332(lgfx_target_t target, int32_t x0, int32_t y0, int32
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     gfx->drawLine(x0, y0, x1, y1, color);
 }
+void lgfx_c_draw_line_rgb565(lgfx_target_t target, int32_t x0, int32_t y0, int32_t x1, int32_t y1, uint16_t color){
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    gfx->drawLine(x0, y0, x1, y1, color);
+}
 void lgfx_c_draw_line_rgb888(lgfx_target_t target, int32_t x0, int32_t y0, int32_t x1, int32_t y1, uint32_t color){
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     gfx->drawLine(x0, y0, x1, y1, color);
@@ -125,11 +587,46 @@ void lgfx_c_push_image_rgb888(lgfx_target_t target, int32_t x, int32_t y, int32_
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     gfx->pushImage(x, y, w, h, reinterpret_cast<const rgb888_t*>(data));
 }
+void lgfx_c_push_image_rgb565_dma(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h, const uint16_t* data) {
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    gfx->pushImageDMA(x, y, w, h, reinterpret_cast<const rgb565_t*>(data));
+}
+void lgfx_c_push_sprite_dma(lgfx_target_t target, lgfx_target_t dst, int32_t x, int32_t y) {
+    auto sprite = static_cast<LGFX_Sprite*>(reinterpret_cast<LovyanGFX*>(target));
+    auto gfx = reinterpret_cast<LovyanGFX*>(dst);
+    gfx->pushImageDMA(x, y, sprite->width(), sprite->height(), sprite->getBuffer(), sprite->getColorDepth(), sprite->getPalette());
+}
+bool lgfx_c_dma_busy(lgfx_target_t target) {
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    return gfx->dmaBusy();
+}
+void lgfx_c_wait_dma(lgfx_target_t target) {
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    gfx->waitDMA();
+}
 
 bool lgfx_c_draw_png(lgfx_target_t target, const uint8_t *data, uint32_t len, int32_t x, int32_t y, int32_t maxWidth, int32_t maxHeight, int32_t offX, int32_t offY, float scale_x, float scale_y, ::textdatum_t datum) {
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     return gfx->drawPng(data, len, x, y, maxWidth, maxHeight, offX, offY, scale_x, scale_y, static_cast<datum_t>(datum));
 }
+bool lgfx_c_draw_jpg(lgfx_target_t target, const uint8_t *data, uint32_t len, int32_t x, int32_t y, int32_t maxWidth, int32_t maxHeight, int32_t offX, int32_t offY, float scale_x, float scale_y, ::textdatum_t datum) {
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    return gfx->drawJpg(data, len, x, y, maxWidth, maxHeight, offX, offY, scale_x, scale_y, static_cast<datum_t>(datum));
+}
+uint32_t lgfx_c_read_pixel_rgb888(lgfx_target_t target, int32_t x, int32_t y) {
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    auto color = gfx->readPixelRGB(x, y);
+    return (static_cast<uint32_t>(color.R8()) << 16) | (static_cast<uint32_t>(color.G8()) << 8) | color.B8();
+}
+void lgfx_c_read_rect_rgb888(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h, uint32_t* data) {
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    for( int32_t j = 0; j < h; j++ ) {
+        for( int32_t i = 0; i < w; i++ ) {
+            auto color = gfx->readPixelRGB(x + i, y + j);
+            *data++ = (static_cast<uint32_t>(color.R8()) << 16) | (static_cast<uint32_t>(color.G8()) << 8) | color.B8();
+        }
+    }
+}
 
 lgfx_target_t lgfx_c_create_sprite(lgfx_target_t target, int32_t w, int32_t h) {
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
@@ -148,10 +645,54 @@ lgfx_target_t lgfx_c_create_sprite_static(lgfx_target_t target, int32_t w, int32
     sprite->setBuffer(buffer, w, h, bpp);
     return reinterpret_cast<lgfx_target_t>(static_cast<LovyanGFX*>(sprite));
 }
+lgfx_target_t lgfx_c_create_sprite_with_depth(lgfx_target_t target, int32_t w, int32_t h, uint8_t bpp) {
+    // target may be null to create a sprite without parent.
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    auto sprite = new LGFX_Sprite(gfx);
+    if( sprite == nullptr ) return nullptr;
+    sprite->setColorDepth(bpp);
+    if( sprite->createSprite(w, h) == nullptr ) {
+        delete sprite;
+        return nullptr;
+    }
+    return reinterpret_cast<lgfx_target_t>(static_cast<LovyanGFX*>(sprite));
+}
+uint32_t lgfx_c_sprite_read_pixel_value(lgfx_target_t target, int32_t x, int32_t y) {
+    auto sprite = static_cast<LGFX_Sprite*>(reinterpret_cast<LovyanGFX*>(target));
+    return sprite->readPixelValue(x, y);
+}
+void lgfx_c_sprite_read_values_u8(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h, uint8_t* data) {
+    auto sprite = static_cast<LGFX_Sprite*>(reinterpret_cast<LovyanGFX*>(target));
+    for( int32_t j = 0; j < h; j++ ) {
+        for( int32_t i = 0; i < w; i++ ) {
+            *data++ = static_cast<uint8_t>(sprite->readPixelValue(x + i, y + j));
+        }
+    }
+}
 void lgfx_c_push_sprite(lgfx_target_t target, int32_t x, int32_t y) {
     auto sprite = static_cast<LGFX_Sprite*>(reinterpret_cast<LovyanGFX*>(target));
     sprite->pushSprite(x, y);
 }
+void lgfx_c_push_sprite_to(lgfx_target_t target, lgfx_target_t dst, int32_t x, int32_t y) {
+    auto sprite = static_cast<LGFX_Sprite*>(reinterpret_cast<LovyanGFX*>(target));
+    sprite->pushSprite(reinterpret_cast<LovyanGFX*>(dst), x, y);
+}
+void lgfx_c_push_sprite_region(lgfx_target_t target, lgfx_target_t dst, int32_t x, int32_t y, int32_t rx, int32_t ry, int32_t rw, int32_t rh) {
+    auto sprite = static_cast<LGFX_Sprite*>(reinterpret_cast<LovyanGFX*>(target));
+    auto gfx = reinterpret_cast<LovyanGFX*>(dst);
+    // Restrict the transfer by the clip rect of the destination within the clip rect set by the user, and restore it.
+    int32_t cx, cy, cw, ch;
+    gfx->getClipRect(&cx, &cy, &cw, &ch);
+    int32_t left = std::max(x + rx, cx);
+    int32_t top = std::max(y + ry, cy);
+    int32_t right = std::min(x + rx + rw, cx + cw);
+    int32_t bottom = std::min(y + ry + rh, cy + ch);
+    if( left < right && top < bottom ) {
+        gfx->setClipRect(left, top, right - left, bottom - top);
+        sprite->pushSprite(gfx, x, y);
+        gfx->setClipRect(cx, cy, cw, ch);
+    }
+}
 void lgfx_c_delete_sprite(lgfx_target_t target) {
     if( target != nullptr ) {
         auto sprite = static_cast<LGFX_Sprite*>(reinterpret_cast<LovyanGFX*>(target));
@@ -179,6 +720,10 @@ size_t lgfx_c_draw_char_rgb332(lgfx_target_t target, int32_t x, int32_t y, uint1
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     return gfx->drawChar(x, y, unicode, color, bg, size_x, size_y);
 }
+size_t lgfx_c_draw_char_rgb565(lgfx_target_t target, int32_t x, int32_t y, uint16_t unicode, uint16_t color, uint16_t bg, float size_x, float size_y) {
+    auto gfx = reinterpret_cast<LovyanGFX*>(target);
+    return gfx->drawChar(x, y, unicode, rgb565_t(color), rgb565_t(bg), size_x, size_y);
+}
 size_t lgfx_c_draw_char_rgb888(lgfx_target_t target, int32_t x, int32_t y, uint16_t unicode, uint32_t color, uint32_t bg, float size_x, float size_y) {
     auto gfx = reinterpret_cast<LovyanGFX*>(target);
     return gfx->drawChar(x, y, unicode, color, bg, size_x, size_y);
@@ -208,4 +753,39 @@ bool lgfx_c_font_update_font_metrics(const void* font, font_metrics_t *metrics,
     if( font == nullptr ) return false;
     auto ifont = reinterpret_cast<const IFont*>(font);
     return ifont->updateFontMetric(reinterpret_cast<lgfx::v1::FontMetrics*>(metrics), unicode);
-}
\ No newline at end of file
+}
+
+bool lgfx_c_has_touch(lgfx_target_t target) {
+#ifdef LGFX_SDL
+    // Panel_sdl reports the mouse as the touch panel.
+    return true;
+#else
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    return gfx->touch() != nullptr;
+#endif
+}
+uint_fast8_t lgfx_c_get_touch(lgfx_target_t target, ::touch_point_t *points, uint_fast8_t count) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    return gfx->getTouch(reinterpret_cast<lgfx::v1::touch_point_t*>(points), count);
+}
+void lgfx_c_calibrate_touch(lgfx_target_t target, uint16_t *parameters, uint32_t fg, uint32_t bg, uint8_t size) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->calibrateTouch(parameters, rgb888_t(fg), rgb888_t(bg), size);
+}
+void lgfx_c_set_touch_calibrate(lgfx_target_t target, uint16_t *parameters) {
+    auto gfx = static_cast<LGFX_Device*>(reinterpret_cast<LovyanGFX*>(target));
+    gfx->setTouchCalibrate(parameters);
+}
+
+void lgfx_c_gpio_set_input(int32_t pin, bool pullup) {
+#ifndef LGFX_SDL
+    lgfx::pinMode(pin, pullup ? lgfx::pin_mode_t::input_pullup : lgfx::pin_mode_t::input);
+#endif
+}
+bool lgfx_c_gpio_in(int32_t pin) {
+#ifndef LGFX_SDL
+    return lgfx::gpio_in(pin);
+#else
+    return false;
+#endif
+}
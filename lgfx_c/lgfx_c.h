@@ -54,15 +54,176 @@ typedef struct font_metrics
     int16_t baseline;
 } font_metrics_t;
 
+typedef struct touch_point
+{
+    int16_t x;
+    int16_t y;
+    uint16_t size;
+    uint16_t id;
+} touch_point_t;
+
+typedef enum bus_type
+{
+    bus_spi       = 0,
+    bus_i2c       = 1,
+    bus_parallel8 = 2,
+    bus_rgb       = 3,
+} bus_type_t;
+
+typedef struct bus_config
+{
+    bus_type_t type;
+    int32_t port;           // SPI host, I2C port or parallel port
+    uint32_t freq_write;
+    uint32_t freq_read;
+    uint8_t spi_mode;
+    bool spi_3wire;
+    bool use_lock;
+    uint8_t dma_channel;
+    uint8_t i2c_addr;
+    int16_t pin_sclk;
+    int16_t pin_mosi;
+    int16_t pin_miso;
+    int16_t pin_dc;
+    int16_t pin_sda;
+    int16_t pin_scl;
+    int16_t pin_wr;
+    int16_t pin_rd;
+    int16_t pin_rs;
+    int16_t pin_d[16];      // D0-D7 for parallel8, D0-D15 for RGB
+    int16_t pin_henable;
+    int16_t pin_vsync;
+    int16_t pin_hsync;
+    int16_t pin_pclk;
+    uint8_t hsync_polarity;
+    uint8_t hsync_front_porch;
+    uint8_t hsync_pulse_width;
+    uint8_t hsync_back_porch;
+    uint8_t vsync_polarity;
+    uint8_t vsync_front_porch;
+    uint8_t vsync_pulse_width;
+    uint8_t vsync_back_porch;
+    bool pclk_idle_high;
+    bool de_idle_high;
+} bus_config_t;
+
+typedef enum panel_driver
+{
+    panel_ili9341 = 0,
+    panel_ili9342 = 1,
+    panel_ili9488 = 2,
+    panel_st7735s = 3,
+    panel_st7789  = 4,
+    panel_st7796  = 5,
+    panel_gc9a01  = 6,
+    panel_ssd1306 = 7,
+    panel_sh110x  = 8,
+    panel_rgb     = 9,
+} panel_driver_t;
+
+typedef struct panel_config
+{
+    panel_driver_t driver;
+    int16_t pin_cs;
+    int16_t pin_rst;
+    int16_t pin_busy;
+    uint16_t memory_width;
+    uint16_t memory_height;
+    uint16_t panel_width;
+    uint16_t panel_height;
+    int16_t offset_x;
+    int16_t offset_y;
+    uint8_t offset_rotation;
+    bool readable;
+    bool invert;
+    bool rgb_order;
+    bool dlen_16bit;
+    bool bus_shared;
+} panel_config_t;
+
+typedef struct light_config
+{
+    int16_t pin_bl;
+    bool invert;
+    uint32_t freq;
+    uint8_t pwm_channel;
+} light_config_t;
+
+typedef enum touch_driver
+{
+    touch_ft5x06  = 0,
+    touch_gt911   = 1,
+    touch_xpt2046 = 2,
+    touch_cst816s = 3,
+} touch_driver_t;
+
+typedef struct touch_config
+{
+    touch_driver_t driver;
+    int16_t x_min;
+    int16_t x_max;
+    int16_t y_min;
+    int16_t y_max;
+    int16_t pin_int;
+    int16_t pin_rst;
+    int16_t pin_cs;
+    int16_t pin_sclk;
+    int16_t pin_mosi;
+    int16_t pin_miso;
+    int16_t pin_sda;
+    int16_t pin_scl;
+    int32_t port;           // SPI host or I2C port
+    uint32_t freq;
+    uint8_t i2c_addr;
+    uint8_t offset_rotation;
+    bool bus_shared;
+} touch_config_t;
+
 typedef struct lgfx_target *lgfx_target_t;
 
+typedef enum event_type
+{
+    event_none     = 0,
+    event_quit     = 1,
+    event_key_down = 2,
+    event_key_up   = 3,
+    event_close    = 4,
+} event_type_t;
+
+typedef struct event
+{
+    event_type_t type;
+    int32_t keycode;
+    // Display whose window received the event.
+    lgfx_target_t target;
+} event_t;
+
+// The setup functions return NULL if the panel failed to be initialized.
 lgfx_target_t lgfx_c_setup(void);
 lgfx_target_t lgfx_c_setup_with_size(int width, int height);
+// light and touch may be NULL. Returns NULL if the configuration is not supported on this platform.
+// The simulator only uses the panel size.
+lgfx_target_t lgfx_c_setup_with_config(const bus_config_t *bus, const panel_config_t *panel, const light_config_t *light, const touch_config_t *touch);
+// Releases the device created by lgfx_c_setup*, its bus and the simulator window.
+// May be called on any thread. The simulator window is destroyed by the next lgfx_c_panel_sdl_event_handler.
+void lgfx_c_free(lgfx_target_t target);
 
 epd_mode_t lgfx_c_get_epd_mode(lgfx_target_t target);
 void lgfx_c_set_epd_mode(lgfx_target_t target, enum epd_mode epd_mode);
 bool lgfx_c_is_epd(lgfx_target_t target);
+void lgfx_c_display(lgfx_target_t target);
+void lgfx_c_display_region(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h);
+void lgfx_c_wait_display(lgfx_target_t target);
+bool lgfx_c_display_busy(lgfx_target_t target);
+void lgfx_c_set_auto_display(lgfx_target_t target, bool enable);
 void lgfx_c_set_rotation(lgfx_target_t target, uint_fast8_t rotation);
+uint8_t lgfx_c_get_rotation(lgfx_target_t target);
+void lgfx_c_set_brightness(lgfx_target_t target, uint8_t brightness);
+uint8_t lgfx_c_get_brightness(lgfx_target_t target);
+void lgfx_c_sleep(lgfx_target_t target);
+void lgfx_c_wakeup(lgfx_target_t target);
+void lgfx_c_power_save(lgfx_target_t target, bool enable);
+void lgfx_c_invert_display(lgfx_target_t target, bool invert);
 
 int32_t lgfx_c_width(lgfx_target_t target);
 int32_t lgfx_c_height(lgfx_target_t target);
@@ -70,21 +231,40 @@ int32_t lgfx_c_height(lgfx_target_t target);
 int32_t lgfx_c_font_height(lgfx_target_t target);
 
 void lgfx_c_clear_rgb332(lgfx_target_t target, uint8_t color);
+void lgfx_c_clear_rgb565(lgfx_target_t target, uint16_t color);
 void lgfx_c_clear_rgb888(lgfx_target_t target, uint32_t color);
 void lgfx_c_fill_rect_rgb332(lgfx_target_t target, int32_t left, int32_t top, int32_t width, int32_t height, uint8_t color);
+void lgfx_c_fill_rect_rgb565(lgfx_target_t target, int32_t left, int32_t top, int32_t width, int32_t height, uint16_t color);
 void lgfx_c_fill_rect_rgb888(lgfx_target_t target, int32_t left, int32_t top, int32_t width, int32_t height, uint32_t color);
 void lgfx_c_draw_line_rgb332(lgfx_target_t target, int32_t x0, int32_t y0, int32_t x1, int32_t y1, uint8_t color);
+void lgfx_c_draw_line_rgb565(lgfx_target_t target, int32_t x0, int32_t y0, int32_t x1, int32_t y1, uint16_t color);
 void lgfx_c_draw_line_rgb888(lgfx_target_t target, int32_t x0, int32_t y0, int32_t x1, int32_t y1, uint32_t color);
 
 void lgfx_c_push_image_grayscale(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h, const uint8_t* data);
 void lgfx_c_push_image_rgb332(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h, const uint8_t* data);
 void lgfx_c_push_image_rgb888(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h, const uint8_t* data);
+// DMA transfers. The data must be kept until lgfx_c_dma_busy returns false, within a write transaction.
+void lgfx_c_push_image_rgb565_dma(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h, const uint16_t* data);
+void lgfx_c_push_sprite_dma(lgfx_target_t target, lgfx_target_t dst, int32_t x, int32_t y);
+bool lgfx_c_dma_busy(lgfx_target_t target);
+void lgfx_c_wait_dma(lgfx_target_t target);
 
 bool lgfx_c_draw_png(lgfx_target_t target, const uint8_t *data, uint32_t len, int32_t x, int32_t y, int32_t maxWidth, int32_t maxHeight, int32_t offX, int32_t offY, float scale_x, float scale_y, textdatum_t datum);
+bool lgfx_c_draw_jpg(lgfx_target_t target, const uint8_t *data, uint32_t len, int32_t x, int32_t y, int32_t maxWidth, int32_t maxHeight, int32_t offX, int32_t offY, float scale_x, float scale_y, textdatum_t datum);
+uint32_t lgfx_c_read_pixel_rgb888(lgfx_target_t target, int32_t x, int32_t y);
+// Reads the pixels of the rectangle as 0x00RRGGBB in the row-major order. `data` must have w * h elements.
+void lgfx_c_read_rect_rgb888(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h, uint32_t* data);
 
 lgfx_target_t lgfx_c_create_sprite(lgfx_target_t target, int32_t w, int32_t h);
 lgfx_target_t lgfx_c_create_sprite_static(lgfx_target_t target, int32_t w, int32_t h, void* buffer, uint8_t bpp);
+lgfx_target_t lgfx_c_create_sprite_with_depth(lgfx_target_t target, int32_t w, int32_t h, uint8_t bpp);
+uint32_t lgfx_c_sprite_read_pixel_value(lgfx_target_t target, int32_t x, int32_t y);
+// Reads the low 8 bits of the pixel values of the rectangle in the row-major order. `data` must have w * h elements.
+void lgfx_c_sprite_read_values_u8(lgfx_target_t target, int32_t x, int32_t y, int32_t w, int32_t h, uint8_t* data);
 void lgfx_c_push_sprite(lgfx_target_t target, int32_t x, int32_t y);
+void lgfx_c_push_sprite_to(lgfx_target_t target, lgfx_target_t dst, int32_t x, int32_t y);
+// Pushes only the region (rx, ry, rw, rh) of the sprite, in the sprite coordinates.
+void lgfx_c_push_sprite_region(lgfx_target_t target, lgfx_target_t dst, int32_t x, int32_t y, int32_t rx, int32_t ry, int32_t rw, int32_t rh);
 void lgfx_c_delete_sprite(lgfx_target_t target);
 
 void lgfx_c_start_write(lgfx_target_t target);
@@ -95,6 +275,7 @@ void lgfx_c_set_cursor(lgfx_target_t target, int32_t x, int32_t y);
 void lgfx_c_set_text_size(lgfx_target_t target, float sx, float sy);
 void lgfx_c_set_text_datum(lgfx_target_t target, textdatum_t datum);
 size_t lgfx_c_draw_char_rgb332(lgfx_target_t target, int32_t x, int32_t y, uint16_t unicode, uint8_t color, uint8_t bg, float size_x, float size_y);
+size_t lgfx_c_draw_char_rgb565(lgfx_target_t target, int32_t x, int32_t y, uint16_t unicode, uint16_t color, uint16_t bg, float size_x, float size_y);
 size_t lgfx_c_draw_char_rgb888(lgfx_target_t target, int32_t x, int32_t y, uint16_t unicode, uint32_t color, uint32_t bg, float size_x, float size_y);
 
 const void* lgfx_c_get_font(lgfx_target_t target);
@@ -102,7 +283,18 @@ bool lgfx_c_set_font(lgfx_target_t target, const void* font);
 void lgfx_c_font_get_default_metrics(const void* font, font_metrics_t *metrics);
 bool lgfx_c_font_update_font_metrics(const void* font, font_metrics_t *metrics, uint16_t unicode);
 
+bool lgfx_c_has_touch(lgfx_target_t target);
+uint_fast8_t lgfx_c_get_touch(lgfx_target_t target, touch_point_t *points, uint_fast8_t count);
+void lgfx_c_calibrate_touch(lgfx_target_t target, uint16_t *parameters, uint32_t fg, uint32_t bg, uint8_t size);
+void lgfx_c_set_touch_calibrate(lgfx_target_t target, uint16_t *parameters);
+
+void lgfx_c_gpio_set_input(int32_t pin, bool pullup);
+bool lgfx_c_gpio_in(int32_t pin);
+
+// Must be called on the main thread. Also destroys the windows of the freed devices.
 void lgfx_c_panel_sdl_event_handler(void);
+// Pops the oldest event of the window of target, including the quit request delivered to every display.
+bool lgfx_c_panel_sdl_poll_event(lgfx_target_t target, event_t *event);
 
 #ifdef __cplusplus
 }
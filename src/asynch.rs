@@ -0,0 +1,231 @@
+//! Async façade of the display for the single-threaded executors such as Embassy or tokio's `LocalSet`.
+//!
+//! No executor is required. Waiting for the lock or the DMA completion yields to the executor
+//! and is retried on the next poll, so the other tasks can run meanwhile.
+//! The futures holding `LgfxGuard` are not `Send`.
+//!
+//! There is no interrupt to wake the futures, so they wake themselves on every `Poll::Pending`.
+//! Thus the executor polls them in a busy loop while they are waiting, and does not sleep until they complete.
+//!
+//! The data transferred with DMA is owned by the futures or `'static`, since the futures may be leaked
+//! during the transfer without running their destructors.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::{
+    lgfx_c_dma_busy, lgfx_c_push_image_rgb565_dma, lgfx_c_push_sprite_dma, lgfx_c_wait_dma, Gfx,
+    LgfxError, LgfxGuard, LgfxTarget, SharedLgfxTarget, Sprite,
+};
+
+/// Future which retries `try_acquire` until it stops failing with `LgfxError::WouldBlock`.
+/// Busy-polls: it wakes itself on every `Poll::Pending`.
+pub(crate) struct Acquire<F> {
+    try_acquire: F,
+}
+
+impl<F, G> Acquire<F>
+where
+    F: FnMut() -> Result<G, LgfxError>,
+{
+    pub(crate) fn new(try_acquire: F) -> Self {
+        Self { try_acquire }
+    }
+}
+
+impl<F> Unpin for Acquire<F> {}
+
+impl<F, G> Future for Acquire<F>
+where
+    F: FnMut() -> Result<G, LgfxError>,
+{
+    type Output = Result<G, LgfxError>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match (self.try_acquire)() {
+            Err(LgfxError::WouldBlock) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+            result => Poll::Ready(result),
+        }
+    }
+}
+
+/// Future which completes when the DMA transfer started through the guard is finished.
+/// Busy-polls like `Acquire`.
+///
+/// Waits for the transfer synchronously if dropped before completion,
+/// so the transferred data is never released during the transfer.
+struct DmaTransfer<'g, 'a> {
+    guard: &'g mut LgfxGuard<'a>,
+    done: bool,
+}
+
+impl<'g, 'a> Future for DmaTransfer<'g, 'a> {
+    type Output = ();
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if unsafe { lgfx_c_dma_busy(self.guard.target()) } {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        } else {
+            self.done = true;
+            Poll::Ready(())
+        }
+    }
+}
+
+impl<'g, 'a> Drop for DmaTransfer<'g, 'a> {
+    fn drop(&mut self) {
+        if !self.done {
+            unsafe { lgfx_c_wait_dma(self.guard.target()) };
+        }
+    }
+}
+
+/// Async access to `Gfx`.
+///
+/// The futures busy-poll while waiting for the lock or the DMA transfer. See the module documentation.
+pub struct AsyncGfx<'a> {
+    shared: SharedLgfxTarget<'a>,
+}
+
+impl<'a> AsyncGfx<'a> {
+    pub fn new(gfx: &'a Gfx) -> Self {
+        Self { shared: gfx.as_shared() }
+    }
+    /// Locks the display. Yields while the display is locked by others.
+    pub async fn lock(&self) -> Result<LgfxGuard<'_>, LgfxError> {
+        Acquire::new(|| self.shared.try_lock()).await
+    }
+    /// Locks the display and starts a write transaction. See `SharedLgfxTarget::lock_without_auto_update`.
+    pub async fn lock_without_auto_update(&self) -> Result<LgfxGuard<'_>, LgfxError> {
+        Acquire::new(|| self.shared.try_lock_without_auto_update()).await
+    }
+    /// Transfers the RGB565 image with DMA, and completes when the transfer is finished.
+    pub async fn push_image(&self, x: i32, y: i32, w: i32, h: i32, data: &'static [u16]) -> Result<(), LgfxError> {
+        match image_len(w, h) {
+            Some(len) if len <= data.len() => {}
+            _ => return Err(LgfxError::InvalidImage),
+        }
+        let mut guard = self.lock_without_auto_update().await?;
+        unsafe { lgfx_c_push_image_rgb565_dma(guard.target(), x, y, w, h, data.as_ptr()) };
+        DmaTransfer { guard: &mut guard, done: false }.await;
+        Ok(())
+    }
+    /// Transfers the sprite with DMA, and completes when the transfer is finished.
+    /// The sprite is moved into the future during the transfer, and given back on both success and failure.
+    pub async fn push_sprite(&self, sprite: Sprite, x: i32, y: i32) -> Result<Sprite, (LgfxError, Sprite)> {
+        let mut guard = match self.lock_without_auto_update().await {
+            Ok(guard) => guard,
+            Err(error) => return Err((error, sprite)),
+        };
+        unsafe { lgfx_c_push_sprite_dma(sprite.target(), guard.target(), x, y) };
+        DmaTransfer { guard: &mut guard, done: false }.await;
+        Ok(sprite)
+    }
+}
+
+/// Number of the pixels of the image, or `None` if the size is negative or overflows.
+fn image_len(w: i32, h: i32) -> Option<usize> {
+    let w = usize::try_from(w).ok()?;
+    let h = usize::try_from(h).ok()?;
+    w.checked_mul(h)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+
+    use super::*;
+
+    struct CountingWaker {
+        wakes: std::sync::atomic::AtomicUsize,
+    }
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.wake_by_ref();
+        }
+        fn wake_by_ref(self: &Arc<Self>) {
+            self.wakes.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    /// Polls the future until ready, and returns the output and the number of the polls.
+    fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+        let counter = Arc::new(CountingWaker { wakes: Default::default() });
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+        let mut future = core::pin::pin!(future);
+        let mut polls = 0;
+        loop {
+            polls += 1;
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return (output, polls);
+            }
+            // Pending futures must arrange to be polled again.
+            assert_eq!(counter.wakes.load(std::sync::atomic::Ordering::SeqCst), polls);
+        }
+    }
+
+    /// Lock which is held by others for `busy` attempts.
+    fn fake_lock(busy: u32) -> impl FnMut() -> Result<u32, LgfxError> {
+        let attempts = Cell::new(0);
+        move || {
+            attempts.set(attempts.get() + 1);
+            if attempts.get() <= busy {
+                Err(LgfxError::WouldBlock)
+            } else {
+                Ok(attempts.get())
+            }
+        }
+    }
+
+    #[test]
+    fn acquires_free_lock_immediately() {
+        let (result, polls) = block_on(Acquire::new(fake_lock(0)));
+        assert_eq!(result.ok(), Some(1));
+        assert_eq!(polls, 1);
+    }
+
+    #[test]
+    fn yields_while_locked_by_others() {
+        let (result, polls) = block_on(Acquire::new(fake_lock(3)));
+        assert_eq!(result.ok(), Some(4));
+        assert_eq!(polls, 4);
+    }
+
+    #[test]
+    fn fails_without_retrying_on_other_errors() {
+        let (result, polls) = block_on(Acquire::new(|| Err::<(), _>(LgfxError::Poisoned)));
+        assert!(matches!(result, Err(LgfxError::Poisoned)));
+        assert_eq!(polls, 1);
+    }
+
+    #[test]
+    fn interleaves_with_other_tasks() {
+        // Another task releases the lock while the lock future is pending.
+        let locked = Cell::new(true);
+        let mut acquire = Acquire::new(|| if locked.get() { Err(LgfxError::WouldBlock) } else { Ok(()) });
+        let waker = Waker::from(Arc::new(CountingWaker { wakes: Default::default() }));
+        let mut cx = Context::from_waker(&waker);
+        assert!(Pin::new(&mut acquire).poll(&mut cx).is_pending());
+        locked.set(false);
+        assert!(matches!(Pin::new(&mut acquire).poll(&mut cx), Poll::Ready(Ok(()))));
+    }
+
+    #[test]
+    fn rejects_invalid_image_size() {
+        assert_eq!(image_len(320, 240), Some(320 * 240));
+        assert_eq!(image_len(0, 240), Some(0));
+        assert_eq!(image_len(-1, 240), None);
+        assert_eq!(image_len(320, -1), None);
+        // 65536 * 65536 wraps around to 0 with 32-bit usize.
+        #[cfg(target_pointer_width = "32")]
+        assert_eq!(image_len(65536, 65536), None);
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(image_len(65536, 65536), Some(1 << 32));
+    }
+}
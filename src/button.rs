@@ -0,0 +1,171 @@
+use crate::event::Event;
+
+/// Logical button ID, which is independent of the physical key or GPIO.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ButtonId(pub u8);
+
+impl ButtonId {
+    pub const A: ButtonId = ButtonId(0);
+    pub const B: ButtonId = ButtonId(1);
+    pub const C: ButtonId = ButtonId(2);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonEvent {
+    Pressed(ButtonId),
+    Released(ButtonId),
+}
+
+/// Mapping from the SDL keycodes to the logical buttons to simulate the buttons on the simulator.
+#[derive(Clone, Debug, Default)]
+pub struct KeyButtonMap {
+    bindings: Vec<(i32, ButtonId)>,
+}
+
+impl KeyButtonMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Maps the `A`, `S` and `D` keys to the M5Stack buttons A, B and C respectively.
+    pub fn m5stack() -> Self {
+        // SDL keycodes of the letter keys are their lowercase ASCII codes.
+        Self::new()
+            .bind(b'a' as i32, ButtonId::A)
+            .bind(b's' as i32, ButtonId::B)
+            .bind(b'd' as i32, ButtonId::C)
+    }
+    /// Binds the key to the button. A button can be bound to multiple keys.
+    pub fn bind(mut self, keycode: i32, button: ButtonId) -> Self {
+        self.bindings.retain(|(bound, _)| *bound != keycode);
+        self.bindings.push((keycode, button));
+        self
+    }
+    pub fn button(&self, keycode: i32) -> Option<ButtonId> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == keycode)
+            .map(|(_, button)| *button)
+    }
+    /// Translates the key event into the button event.
+    pub fn translate(&self, event: &Event) -> Option<ButtonEvent> {
+        match *event {
+            Event::KeyDown { keycode } => self.button(keycode).map(ButtonEvent::Pressed),
+            Event::KeyUp { keycode } => self.button(keycode).map(ButtonEvent::Released),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(target_os = "espidf")]
+pub use gpio::GpioButtons;
+
+#[cfg(target_os = "espidf")]
+mod gpio {
+    use std::time::{Duration, Instant};
+
+    use super::{ButtonEvent, ButtonId};
+    use crate::{lgfx_c_gpio_in, lgfx_c_gpio_set_input};
+
+    struct GpioButton {
+        pin: i32,
+        id: ButtonId,
+        active_low: bool,
+        pressed: bool,
+        last_level: bool,
+        last_change: Instant,
+    }
+
+    /// Reads the buttons connected to GPIOs with debouncing.
+    pub struct GpioButtons {
+        buttons: Vec<GpioButton>,
+        debounce: Duration,
+    }
+
+    impl GpioButtons {
+        pub fn new() -> Self {
+            Self {
+                buttons: Vec::new(),
+                debounce: Duration::from_millis(20),
+            }
+        }
+        /// Buttons A, B and C of M5Stack Basic/Gray/Fire.
+        pub fn m5stack() -> Self {
+            Self::new()
+                .add(39, ButtonId::A, true)
+                .add(38, ButtonId::B, true)
+                .add(37, ButtonId::C, true)
+        }
+        /// Adds the button connected to `pin`. The pin is configured as input with pull-up if `active_low`.
+        pub fn add(mut self, pin: i32, id: ButtonId, active_low: bool) -> Self {
+            unsafe { lgfx_c_gpio_set_input(pin, active_low) };
+            self.buttons.push(GpioButton {
+                pin,
+                id,
+                active_low,
+                pressed: false,
+                last_level: false,
+                last_change: Instant::now(),
+            });
+            self
+        }
+        pub fn debounce(mut self, debounce: Duration) -> Self {
+            self.debounce = debounce;
+            self
+        }
+
+        /// Reads the buttons and calls `f` for each button whose state has changed.
+        pub fn poll(&mut self, mut f: impl FnMut(ButtonEvent)) {
+            let now = Instant::now();
+            for button in self.buttons.iter_mut() {
+                let level = unsafe { lgfx_c_gpio_in(button.pin) } != button.active_low;
+                if level != button.last_level {
+                    button.last_level = level;
+                    button.last_change = now;
+                } else if level != button.pressed && now.duration_since(button.last_change) >= self.debounce {
+                    button.pressed = level;
+                    f(if level {
+                        ButtonEvent::Pressed(button.id)
+                    } else {
+                        ButtonEvent::Released(button.id)
+                    });
+                }
+            }
+        }
+    }
+
+    impl Default for GpioButtons {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebinds_key_to_new_button() {
+        let map = KeyButtonMap::m5stack().bind(b'a' as i32, ButtonId(3));
+        // The binding to the previous button is replaced.
+        assert_eq!(map.button(b'a' as i32), Some(ButtonId(3)));
+        assert_eq!(map.translate(&Event::KeyDown { keycode: b'a' as i32 }), Some(ButtonEvent::Pressed(ButtonId(3))));
+        assert_eq!(map.button(b's' as i32), Some(ButtonId::B));
+    }
+
+    #[test]
+    fn binds_multiple_keys_to_button() {
+        let map = KeyButtonMap::new().bind(1, ButtonId::A).bind(2, ButtonId::A);
+        assert_eq!((map.button(1), map.button(2), map.button(3)), (Some(ButtonId::A), Some(ButtonId::A), None));
+    }
+
+    #[test]
+    fn translates_key_events() {
+        let map = KeyButtonMap::m5stack();
+        let keycode = b'd' as i32;
+        assert_eq!(map.translate(&Event::KeyDown { keycode }), Some(ButtonEvent::Pressed(ButtonId::C)));
+        assert_eq!(map.translate(&Event::KeyUp { keycode }), Some(ButtonEvent::Released(ButtonId::C)));
+        assert_eq!(map.translate(&Event::KeyDown { keycode: b'q' as i32 }), None);
+        assert_eq!(map.translate(&Event::Quit), None);
+    }
+}
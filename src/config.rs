@@ -0,0 +1,532 @@
+use crate::{bus_config_t, light_config_t, panel_config_t, touch_config_t, LgfxError};
+use crate::{
+    bus_type_bus_i2c, bus_type_bus_parallel8, bus_type_bus_rgb, bus_type_bus_spi,
+};
+use crate::{
+    panel_driver_panel_gc9a01, panel_driver_panel_ili9341, panel_driver_panel_ili9342,
+    panel_driver_panel_ili9488, panel_driver_panel_rgb, panel_driver_panel_sh110x,
+    panel_driver_panel_ssd1306, panel_driver_panel_st7735s, panel_driver_panel_st7789,
+    panel_driver_panel_st7796, panel_driver_t,
+};
+use crate::{
+    touch_driver_t, touch_driver_touch_cst816s, touch_driver_touch_ft5x06,
+    touch_driver_touch_gt911, touch_driver_touch_xpt2046,
+};
+
+/// Pin number of the unused pins.
+pub const PIN_UNUSED: i16 = -1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct SpiBusConfig {
+    /// SPI host. e.g. 2 for `VSPI_HOST` on ESP32 or `SPI3_HOST` on ESP32-S3.
+    pub host: i32,
+    pub mode: u8,
+    pub freq_write: u32,
+    pub freq_read: u32,
+    /// Uses MOSI pin for both transmission and reception.
+    pub three_wire: bool,
+    pub use_lock: bool,
+    pub dma_channel: u8,
+    pub pin_sclk: i16,
+    pub pin_mosi: i16,
+    pub pin_miso: i16,
+    pub pin_dc: i16,
+}
+
+impl Default for SpiBusConfig {
+    fn default() -> Self {
+        Self {
+            host: 2,
+            mode: 0,
+            freq_write: 40_000_000,
+            freq_read: 16_000_000,
+            three_wire: false,
+            use_lock: true,
+            // SPI_DMA_CH_AUTO
+            dma_channel: 3,
+            pin_sclk: PIN_UNUSED,
+            pin_mosi: PIN_UNUSED,
+            pin_miso: PIN_UNUSED,
+            pin_dc: PIN_UNUSED,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct I2cBusConfig {
+    pub port: i32,
+    pub freq_write: u32,
+    pub freq_read: u32,
+    pub pin_sda: i16,
+    pub pin_scl: i16,
+    pub address: u8,
+}
+
+impl Default for I2cBusConfig {
+    fn default() -> Self {
+        Self {
+            port: 0,
+            freq_write: 400_000,
+            freq_read: 400_000,
+            pin_sda: PIN_UNUSED,
+            pin_scl: PIN_UNUSED,
+            address: 0x3c,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Parallel8BusConfig {
+    /// I2S port on ESP32, or LCD port on ESP32-S3.
+    pub port: i32,
+    pub freq_write: u32,
+    pub pin_wr: i16,
+    pub pin_rd: i16,
+    pub pin_rs: i16,
+    pub pin_d: [i16; 8],
+}
+
+impl Default for Parallel8BusConfig {
+    fn default() -> Self {
+        Self {
+            port: 0,
+            freq_write: 20_000_000,
+            pin_wr: PIN_UNUSED,
+            pin_rd: PIN_UNUSED,
+            pin_rs: PIN_UNUSED,
+            pin_d: [PIN_UNUSED; 8],
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SyncTiming {
+    pub polarity: u8,
+    pub front_porch: u8,
+    pub pulse_width: u8,
+    pub back_porch: u8,
+}
+
+/// RGB parallel bus. Only supported on ESP32-S3 with `PanelDriver::Rgb`.
+#[derive(Clone, Copy, Debug)]
+pub struct RgbBusConfig {
+    pub freq_write: u32,
+    pub pin_d: [i16; 16],
+    pub pin_henable: i16,
+    pub pin_vsync: i16,
+    pub pin_hsync: i16,
+    pub pin_pclk: i16,
+    pub hsync: SyncTiming,
+    pub vsync: SyncTiming,
+    pub pclk_idle_high: bool,
+    pub de_idle_high: bool,
+}
+
+impl Default for RgbBusConfig {
+    fn default() -> Self {
+        Self {
+            freq_write: 16_000_000,
+            pin_d: [PIN_UNUSED; 16],
+            pin_henable: PIN_UNUSED,
+            pin_vsync: PIN_UNUSED,
+            pin_hsync: PIN_UNUSED,
+            pin_pclk: PIN_UNUSED,
+            hsync: SyncTiming::default(),
+            vsync: SyncTiming::default(),
+            pclk_idle_high: false,
+            de_idle_high: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum BusConfig {
+    Spi(SpiBusConfig),
+    I2c(I2cBusConfig),
+    Parallel8(Parallel8BusConfig),
+    Rgb(RgbBusConfig),
+}
+
+impl BusConfig {
+    fn raw(&self) -> bus_config_t {
+        let mut raw = bus_config_t {
+            type_: 0,
+            port: 0,
+            freq_write: 0,
+            freq_read: 0,
+            spi_mode: 0,
+            spi_3wire: false,
+            use_lock: false,
+            dma_channel: 0,
+            i2c_addr: 0,
+            pin_sclk: PIN_UNUSED,
+            pin_mosi: PIN_UNUSED,
+            pin_miso: PIN_UNUSED,
+            pin_dc: PIN_UNUSED,
+            pin_sda: PIN_UNUSED,
+            pin_scl: PIN_UNUSED,
+            pin_wr: PIN_UNUSED,
+            pin_rd: PIN_UNUSED,
+            pin_rs: PIN_UNUSED,
+            pin_d: [PIN_UNUSED; 16],
+            pin_henable: PIN_UNUSED,
+            pin_vsync: PIN_UNUSED,
+            pin_hsync: PIN_UNUSED,
+            pin_pclk: PIN_UNUSED,
+            hsync_polarity: 0,
+            hsync_front_porch: 0,
+            hsync_pulse_width: 0,
+            hsync_back_porch: 0,
+            vsync_polarity: 0,
+            vsync_front_porch: 0,
+            vsync_pulse_width: 0,
+            vsync_back_porch: 0,
+            pclk_idle_high: false,
+            de_idle_high: false,
+        };
+        match self {
+            Self::Spi(config) => {
+                raw.type_ = bus_type_bus_spi;
+                raw.port = config.host;
+                raw.freq_write = config.freq_write;
+                raw.freq_read = config.freq_read;
+                raw.spi_mode = config.mode;
+                raw.spi_3wire = config.three_wire;
+                raw.use_lock = config.use_lock;
+                raw.dma_channel = config.dma_channel;
+                raw.pin_sclk = config.pin_sclk;
+                raw.pin_mosi = config.pin_mosi;
+                raw.pin_miso = config.pin_miso;
+                raw.pin_dc = config.pin_dc;
+            }
+            Self::I2c(config) => {
+                raw.type_ = bus_type_bus_i2c;
+                raw.port = config.port;
+                raw.freq_write = config.freq_write;
+                raw.freq_read = config.freq_read;
+                raw.pin_sda = config.pin_sda;
+                raw.pin_scl = config.pin_scl;
+                raw.i2c_addr = config.address;
+            }
+            Self::Parallel8(config) => {
+                raw.type_ = bus_type_bus_parallel8;
+                raw.port = config.port;
+                raw.freq_write = config.freq_write;
+                raw.pin_wr = config.pin_wr;
+                raw.pin_rd = config.pin_rd;
+                raw.pin_rs = config.pin_rs;
+                raw.pin_d[..8].copy_from_slice(&config.pin_d);
+            }
+            Self::Rgb(config) => {
+                raw.type_ = bus_type_bus_rgb;
+                raw.freq_write = config.freq_write;
+                raw.pin_d = config.pin_d;
+                raw.pin_henable = config.pin_henable;
+                raw.pin_vsync = config.pin_vsync;
+                raw.pin_hsync = config.pin_hsync;
+                raw.pin_pclk = config.pin_pclk;
+                raw.hsync_polarity = config.hsync.polarity;
+                raw.hsync_front_porch = config.hsync.front_porch;
+                raw.hsync_pulse_width = config.hsync.pulse_width;
+                raw.hsync_back_porch = config.hsync.back_porch;
+                raw.vsync_polarity = config.vsync.polarity;
+                raw.vsync_front_porch = config.vsync.front_porch;
+                raw.vsync_pulse_width = config.vsync.pulse_width;
+                raw.vsync_back_porch = config.vsync.back_porch;
+                raw.pclk_idle_high = config.pclk_idle_high;
+                raw.de_idle_high = config.de_idle_high;
+            }
+        }
+        raw
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanelDriver {
+    Ili9341,
+    Ili9342,
+    Ili9488,
+    St7735s,
+    St7789,
+    St7796,
+    Gc9a01,
+    Ssd1306,
+    Sh110x,
+    /// Panel connected with `BusConfig::Rgb`.
+    Rgb,
+}
+
+impl From<PanelDriver> for panel_driver_t {
+    fn from(value: PanelDriver) -> Self {
+        match value {
+            PanelDriver::Ili9341 => panel_driver_panel_ili9341,
+            PanelDriver::Ili9342 => panel_driver_panel_ili9342,
+            PanelDriver::Ili9488 => panel_driver_panel_ili9488,
+            PanelDriver::St7735s => panel_driver_panel_st7735s,
+            PanelDriver::St7789 => panel_driver_panel_st7789,
+            PanelDriver::St7796 => panel_driver_panel_st7796,
+            PanelDriver::Gc9a01 => panel_driver_panel_gc9a01,
+            PanelDriver::Ssd1306 => panel_driver_panel_ssd1306,
+            PanelDriver::Sh110x => panel_driver_panel_sh110x,
+            PanelDriver::Rgb => panel_driver_panel_rgb,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PanelConfig {
+    pub driver: PanelDriver,
+    pub pin_cs: i16,
+    pub pin_rst: i16,
+    pub pin_busy: i16,
+    /// Size of the memory of the driver IC.
+    pub memory_width: u16,
+    pub memory_height: u16,
+    /// Size of the visible area of the panel.
+    pub panel_width: u16,
+    pub panel_height: u16,
+    pub offset_x: i16,
+    pub offset_y: i16,
+    /// Rotation applied in addition to `set_rotation`, for the panels mounted rotated.
+    pub offset_rotation: u8,
+    pub readable: bool,
+    pub invert: bool,
+    /// Swaps the order of red and blue.
+    pub rgb_order: bool,
+    pub dlen_16bit: bool,
+    /// Set true if the bus is shared with other devices such as SD card.
+    pub bus_shared: bool,
+}
+
+impl PanelConfig {
+    pub fn new(driver: PanelDriver, width: u16, height: u16) -> Self {
+        Self {
+            driver,
+            pin_cs: PIN_UNUSED,
+            pin_rst: PIN_UNUSED,
+            pin_busy: PIN_UNUSED,
+            memory_width: width,
+            memory_height: height,
+            panel_width: width,
+            panel_height: height,
+            offset_x: 0,
+            offset_y: 0,
+            offset_rotation: 0,
+            readable: false,
+            invert: false,
+            rgb_order: false,
+            dlen_16bit: false,
+            bus_shared: false,
+        }
+    }
+    fn raw(&self) -> panel_config_t {
+        panel_config_t {
+            driver: self.driver.into(),
+            pin_cs: self.pin_cs,
+            pin_rst: self.pin_rst,
+            pin_busy: self.pin_busy,
+            memory_width: self.memory_width,
+            memory_height: self.memory_height,
+            panel_width: self.panel_width,
+            panel_height: self.panel_height,
+            offset_x: self.offset_x,
+            offset_y: self.offset_y,
+            offset_rotation: self.offset_rotation,
+            readable: self.readable,
+            invert: self.invert,
+            rgb_order: self.rgb_order,
+            dlen_16bit: self.dlen_16bit,
+            bus_shared: self.bus_shared,
+        }
+    }
+}
+
+/// PWM controlled backlight.
+#[derive(Clone, Copy, Debug)]
+pub struct LightConfig {
+    pub pin_bl: i16,
+    pub invert: bool,
+    pub freq: u32,
+    pub pwm_channel: u8,
+}
+
+impl LightConfig {
+    pub fn new(pin_bl: i16) -> Self {
+        Self {
+            pin_bl,
+            invert: false,
+            freq: 44100,
+            pwm_channel: 7,
+        }
+    }
+    fn raw(&self) -> light_config_t {
+        light_config_t {
+            pin_bl: self.pin_bl,
+            invert: self.invert,
+            freq: self.freq,
+            pwm_channel: self.pwm_channel,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchDriver {
+    Ft5x06,
+    Gt911,
+    /// Connected with SPI. The others are connected with I2C.
+    Xpt2046,
+    Cst816s,
+}
+
+impl From<TouchDriver> for touch_driver_t {
+    fn from(value: TouchDriver) -> Self {
+        match value {
+            TouchDriver::Ft5x06 => touch_driver_touch_ft5x06,
+            TouchDriver::Gt911 => touch_driver_touch_gt911,
+            TouchDriver::Xpt2046 => touch_driver_touch_xpt2046,
+            TouchDriver::Cst816s => touch_driver_touch_cst816s,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TouchConfig {
+    pub driver: TouchDriver,
+    /// Range of the raw coordinates reported by the controller.
+    pub x_min: i16,
+    pub x_max: i16,
+    pub y_min: i16,
+    pub y_max: i16,
+    pub pin_int: i16,
+    pub pin_rst: i16,
+    /// SPI pins. Used only for `TouchDriver::Xpt2046`.
+    pub pin_cs: i16,
+    pub pin_sclk: i16,
+    pub pin_mosi: i16,
+    pub pin_miso: i16,
+    /// I2C pins.
+    pub pin_sda: i16,
+    pub pin_scl: i16,
+    /// SPI host or I2C port.
+    pub port: i32,
+    pub freq: u32,
+    pub i2c_address: u8,
+    pub offset_rotation: u8,
+    pub bus_shared: bool,
+}
+
+impl TouchConfig {
+    /// Creates the configuration reporting the coordinates in `0..width` and `0..height`.
+    /// Fails with `LgfxError::InvalidConfig` if the size exceeds the range of the raw coordinates.
+    pub fn new(driver: TouchDriver, width: u16, height: u16) -> Result<Self, LgfxError> {
+        let (freq, i2c_address) = match driver {
+            TouchDriver::Ft5x06 => (400_000, 0x38),
+            TouchDriver::Gt911 => (400_000, 0x5d),
+            TouchDriver::Xpt2046 => (1_000_000, 0),
+            TouchDriver::Cst816s => (400_000, 0x15),
+        };
+        let x_max = i16::try_from(width.saturating_sub(1)).map_err(|_| LgfxError::InvalidConfig)?;
+        let y_max = i16::try_from(height.saturating_sub(1)).map_err(|_| LgfxError::InvalidConfig)?;
+        Ok(Self {
+            driver,
+            x_min: 0,
+            x_max,
+            y_min: 0,
+            y_max,
+            pin_int: PIN_UNUSED,
+            pin_rst: PIN_UNUSED,
+            pin_cs: PIN_UNUSED,
+            pin_sclk: PIN_UNUSED,
+            pin_mosi: PIN_UNUSED,
+            pin_miso: PIN_UNUSED,
+            pin_sda: PIN_UNUSED,
+            pin_scl: PIN_UNUSED,
+            port: 0,
+            freq,
+            i2c_address,
+            offset_rotation: 0,
+            bus_shared: false,
+        })
+    }
+    fn raw(&self) -> touch_config_t {
+        touch_config_t {
+            driver: self.driver.into(),
+            x_min: self.x_min,
+            x_max: self.x_max,
+            y_min: self.y_min,
+            y_max: self.y_max,
+            pin_int: self.pin_int,
+            pin_rst: self.pin_rst,
+            pin_cs: self.pin_cs,
+            pin_sclk: self.pin_sclk,
+            pin_mosi: self.pin_mosi,
+            pin_miso: self.pin_miso,
+            pin_sda: self.pin_sda,
+            pin_scl: self.pin_scl,
+            port: self.port,
+            freq: self.freq,
+            i2c_addr: self.i2c_address,
+            offset_rotation: self.offset_rotation,
+            bus_shared: self.bus_shared,
+        }
+    }
+}
+
+/// Configuration of the display hardware, used instead of the board autodetection.
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayConfig {
+    pub bus: BusConfig,
+    pub panel: PanelConfig,
+    pub light: Option<LightConfig>,
+    pub touch: Option<TouchConfig>,
+}
+
+impl DisplayConfig {
+    pub fn new(bus: BusConfig, panel: PanelConfig) -> Self {
+        Self {
+            bus,
+            panel,
+            light: None,
+            touch: None,
+        }
+    }
+    pub fn light(mut self, light: LightConfig) -> Self {
+        self.light = Some(light);
+        self
+    }
+    pub fn touch(mut self, touch: TouchConfig) -> Self {
+        self.touch = Some(touch);
+        self
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn raw(
+        &self,
+    ) -> (bus_config_t, panel_config_t, Option<light_config_t>, Option<touch_config_t>) {
+        (
+            self.bus.raw(),
+            self.panel.raw(),
+            self.light.as_ref().map(LightConfig::raw),
+            self.touch.as_ref().map(TouchConfig::raw),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn touch_range_covers_size() {
+        let config = TouchConfig::new(TouchDriver::Ft5x06, 320, 240).unwrap();
+        assert_eq!((config.x_min, config.x_max, config.y_min, config.y_max), (0, 319, 0, 239));
+        let config = TouchConfig::new(TouchDriver::Ft5x06, 0, 32768).unwrap();
+        assert_eq!((config.x_max, config.y_max), (0, i16::MAX));
+    }
+
+    #[test]
+    fn rejects_touch_size_out_of_range() {
+        assert!(matches!(TouchConfig::new(TouchDriver::Gt911, 32769, 240), Err(LgfxError::InvalidConfig)));
+        assert!(matches!(TouchConfig::new(TouchDriver::Gt911, 320, u16::MAX), Err(LgfxError::InvalidConfig)));
+    }
+}
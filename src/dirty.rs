@@ -0,0 +1,397 @@
+//! Dirty rectangle tracking for partial updates.
+//!
+//! `DirtyTracker` records the area touched by each drawing operation, so only the changed regions
+//! have to be transferred from a sprite to the display, or refreshed on an EPD.
+
+use alloc::vec::Vec;
+
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+
+use crate::{
+    lgfx_c_push_sprite_region, textdatum_t, Color, DrawChar, DrawChars, DrawPrimitives, DrawString,
+    FontManupulation, LgfxDisplay, LgfxFont, LgfxGuard, LgfxTarget, Screen, Sprite,
+};
+
+/// Default maximum number of the rectangles kept by `DirtyRegion`.
+pub const DEFAULT_MAX_DIRTY_RECTS: usize = 8;
+
+/// Set of the dirty rectangles in a screen.
+///
+/// Overlapping rectangles are merged into their bounding box.
+/// When the number of the rectangles exceeds the limit, the pair which adds the least area by merging is merged,
+/// so the region always covers every recorded rectangle.
+#[derive(Clone, Debug)]
+pub struct DirtyRegion {
+    bounds: Rectangle,
+    rects: Vec<Rectangle>,
+    max_rects: usize,
+}
+
+impl DirtyRegion {
+    /// Creates an empty region of the screen. The rectangles are clipped by the screen.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            bounds: Rectangle::new(Point::zero(), Size::new(width, height)),
+            rects: Vec::new(),
+            max_rects: DEFAULT_MAX_DIRTY_RECTS,
+        }
+    }
+    /// Sets the maximum number of the rectangles. At least 1.
+    pub fn max_rects(mut self, max_rects: usize) -> Self {
+        self.max_rects = max_rects.max(1);
+        while self.rects.len() > self.max_rects {
+            self.merge_cheapest_pair();
+        }
+        self
+    }
+
+    /// Adds the rectangle to the region.
+    pub fn add(&mut self, rect: &Rectangle) {
+        let mut rect = rect.intersection(&self.bounds);
+        if rect.is_zero_sized() {
+            return;
+        }
+        while let Some(index) = self.rects.iter().position(|other| !other.intersection(&rect).is_zero_sized()) {
+            rect = envelope(&self.rects.swap_remove(index), &rect);
+        }
+        self.rects.push(rect);
+        if self.rects.len() > self.max_rects {
+            self.merge_cheapest_pair();
+        }
+    }
+    /// Marks the whole screen as dirty.
+    pub fn add_all(&mut self) {
+        self.rects.clear();
+        self.rects.push(self.bounds);
+    }
+    pub fn rects(&self) -> &[Rectangle] {
+        &self.rects
+    }
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+    /// Returns the rectangles and clears the region.
+    pub fn take(&mut self) -> Vec<Rectangle> {
+        core::mem::take(&mut self.rects)
+    }
+    /// Returns the smallest rectangle containing the whole region, or `None` if the region is empty.
+    pub fn bounding_box(&self) -> Option<Rectangle> {
+        self.rects.iter().copied().reduce(|region, rect| envelope(&region, &rect))
+    }
+
+    fn merge_cheapest_pair(&mut self) {
+        let mut cheapest = None;
+        for i in 0..self.rects.len() {
+            for j in (i + 1)..self.rects.len() {
+                let (a, b) = (&self.rects[i], &self.rects[j]);
+                let waste = area(&envelope(a, b)) - area(a) - area(b);
+                if !matches!(cheapest, Some((_, _, least)) if least <= waste) {
+                    cheapest = Some((i, j, waste));
+                }
+            }
+        }
+        if let Some((i, j, _)) = cheapest {
+            // Remove the later one first to keep the index of the other.
+            let b = self.rects.swap_remove(j);
+            let a = self.rects.swap_remove(i);
+            // The merged rectangle may overlap the others.
+            self.add(&envelope(&a, &b));
+        }
+    }
+}
+
+/// Records the area of every drawing operation on the target.
+///
+/// Primitives, characters and strings drawn through the tracker, and the embedded-graphics drawing through
+/// `display()` are recorded. The drawing to `inner_mut()`, such as `DrawImage::draw_png`, is not recorded,
+/// so mark it with `mark_dirty`.
+///
+/// ```ignore
+/// let mut canvas = DirtyTracker::new(gfx.create_sprite(width, height)?);
+/// loop {
+///     canvas.draw_string("12:34", 10, 10, ColorRgb888::new(0), None, 2.0, 2.0, textdatum_top_left);
+///     Circle::new(Point::new(40, 40), 8).into_styled(style).draw(&mut canvas.display::<Rgb888>()).ok();
+///     let mut guard = shared.lock()?;
+///     for rect in canvas.flush(&mut guard, 0, 0) {
+///         policy.apply(&mut guard, &rect, Instant::now());
+///         guard.display_region(&rect);
+///     }
+/// }
+/// ```
+pub struct DirtyTracker<T> {
+    target: T,
+    region: DirtyRegion,
+}
+
+impl<T: LgfxTarget> DirtyTracker<T> {
+    pub fn new(target: T) -> Self {
+        let (width, height) = target.size();
+        Self {
+            target,
+            region: DirtyRegion::new(width.max(0) as u32, height.max(0) as u32),
+        }
+    }
+    /// Sets the maximum number of the dirty rectangles. See `DirtyRegion::max_rects`.
+    pub fn max_rects(mut self, max_rects: usize) -> Self {
+        self.region = self.region.max_rects(max_rects);
+        self
+    }
+
+    pub fn region(&self) -> &DirtyRegion {
+        &self.region
+    }
+    pub fn region_mut(&mut self) -> &mut DirtyRegion {
+        &mut self.region
+    }
+    /// Marks the rectangle drawn without the tracker as dirty.
+    pub fn mark_dirty(&mut self, rect: &Rectangle) {
+        self.region.add(rect);
+    }
+    pub fn inner(&self) -> &T {
+        &self.target
+    }
+    /// Returns the target. The drawing to it is not recorded.
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.target
+    }
+    pub fn into_inner(self) -> T {
+        self.target
+    }
+    /// Creates an embedded-graphics adapter which records the drawing.
+    pub fn display<C>(&mut self) -> LgfxDisplay<'_, T, C> {
+        LgfxDisplay::with_color(&mut self.target).tracked(&mut self.region)
+    }
+
+    fn mark_chars(&mut self, s: &str, x: i32, y: i32, size_x: f32, size_y: f32, font: Option<LgfxFont>) {
+        match font {
+            Some(font) => self.region.add(&font.chars_area(s, x, y, size_x, size_y)),
+            // Cannot measure the characters without the font.
+            None => self.region.add_all(),
+        }
+    }
+}
+
+impl DirtyTracker<Sprite> {
+    /// Pushes the dirty regions of the sprite placed at (x, y) to `dst`, and clears the region.
+    /// Returns the pushed rectangles in the coordinates of `dst`, e.g. for `LgfxGuard::display_region`.
+    pub fn flush(&mut self, dst: &mut impl LgfxTarget, x: i32, y: i32) -> Vec<Rectangle> {
+        let mut rects = self.region.take();
+        for rect in rects.iter_mut() {
+            unsafe {
+                lgfx_c_push_sprite_region(
+                    self.target.target(),
+                    dst.target(),
+                    x,
+                    y,
+                    rect.top_left.x,
+                    rect.top_left.y,
+                    rect.size.width as i32,
+                    rect.size.height as i32,
+                );
+            }
+            rect.top_left += Point::new(x, y);
+        }
+        rects
+    }
+}
+
+impl<'a> DirtyTracker<LgfxGuard<'a>> {
+    /// Refreshes the dirty regions of the panel, and clears the region. Returns the refreshed rectangles.
+    /// Only meaningful for the panels with the frame buffer, such as EPD.
+    pub fn refresh(&mut self) -> Vec<Rectangle> {
+        let rects = self.region.take();
+        for rect in rects.iter() {
+            self.target.display_region(rect);
+        }
+        rects
+    }
+}
+
+impl<T: Screen> Screen for DirtyTracker<T> {
+    fn size(&self) -> (i32, i32) {
+        self.target.size()
+    }
+}
+
+impl<T, C> DrawPrimitives<C> for DirtyTracker<T>
+where
+    T: DrawPrimitives<C>,
+    C: Color,
+{
+    fn clear(&mut self, color: C) {
+        self.target.clear(color);
+        self.region.add_all();
+    }
+    fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: C) {
+        self.target.fill_rect(x, y, w, h, color);
+        // LovyanGFX draws the rectangle of the negative size towards the left or top.
+        let (x, w) = if w < 0 { (x + w, -w) } else { (x, w) };
+        let (y, h) = if h < 0 { (y + h, -h) } else { (y, h) };
+        self.region.add(&Rectangle::new(Point::new(x, y), Size::new(w as u32, h as u32)));
+    }
+    fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: C) {
+        self.target.draw_line(x0, y0, x1, y1, color);
+        self.region.add(&Rectangle::with_corners(Point::new(x0, y0), Point::new(x1, y1)));
+    }
+}
+
+impl<T, C> DrawChar<C> for DirtyTracker<T>
+where
+    T: LgfxTarget + DrawChar<C> + FontManupulation,
+    C: Color,
+{
+    fn draw_char(&mut self, c: char, x: i32, y: i32, fg: C, bg: Option<C>, size_x: f32, size_y: f32) -> i32 {
+        let width = self.target.draw_char(c, x, y, fg, bg, size_x, size_y);
+        let font = self.target.get_font().ok();
+        self.mark_chars(c.encode_utf8(&mut [0; 4]), x, y, size_x, size_y, font);
+        width
+    }
+}
+
+impl<T, C> DrawChars<C> for DirtyTracker<T>
+where
+    T: LgfxTarget + DrawChars<C> + FontManupulation,
+    C: Color,
+{
+    fn draw_chars(&mut self, s: &str, x: i32, y: i32, fg: C, bg: Option<C>, size_x: f32, size_y: f32) -> i32 {
+        let width = self.target.draw_chars(s, x, y, fg, bg, size_x, size_y);
+        let font = self.target.get_font().ok();
+        self.mark_chars(s, x, y, size_x, size_y, font);
+        width
+    }
+}
+
+impl<T, C> DrawString<C> for DirtyTracker<T>
+where
+    T: LgfxTarget + DrawString<C> + FontManupulation,
+    C: Color,
+{
+    fn measure_string(&self, s: &str, size_x: f32, size_y: f32) -> (i32, i32) {
+        self.target.measure_string(s, size_x, size_y)
+    }
+    fn draw_string(&mut self, s: &str, x: i32, y: i32, fg: C, bg: Option<C>, size_x: f32, size_y: f32, datum: textdatum_t) -> (i32, i32) {
+        let extent = self.target.draw_string(s, x, y, fg, bg, size_x, size_y, datum);
+        let font = self.target.get_font().ok();
+        let (x, y) = match font {
+            Some(font) => font.string_origin(s, x, y, size_x, size_y, datum),
+            None => (x, y),
+        };
+        self.mark_chars(s, x, y, size_x, size_y, font);
+        extent
+    }
+}
+
+impl<T: FontManupulation> FontManupulation for DirtyTracker<T> {
+    fn font_height(&self) -> i32 {
+        self.target.font_height()
+    }
+    fn get_font(&self) -> Result<LgfxFont, ()> {
+        self.target.get_font()
+    }
+    fn set_font(&mut self, font: LgfxFont) -> Result<(), ()> {
+        self.target.set_font(font)
+    }
+    fn set_text_size(&mut self, sx: f32, sy: f32) {
+        self.target.set_text_size(sx, sy)
+    }
+    fn set_text_datum(&mut self, datum: textdatum_t) {
+        self.target.set_text_datum(datum)
+    }
+}
+
+/// Returns the smallest rectangle containing both rectangles.
+pub(crate) fn envelope(a: &Rectangle, b: &Rectangle) -> Rectangle {
+    let top_left = Point::new(a.top_left.x.min(b.top_left.x), a.top_left.y.min(b.top_left.y));
+    let bottom_right = Point::new(
+        (a.top_left.x + a.size.width as i32).max(b.top_left.x + b.size.width as i32),
+        (a.top_left.y + a.size.height as i32).max(b.top_left.y + b.size.height as i32),
+    );
+    Rectangle::new(top_left, Size::new((bottom_right.x - top_left.x) as u32, (bottom_right.y - top_left.y) as u32))
+}
+
+fn area(rect: &Rectangle) -> u64 {
+    rect.size.width as u64 * rect.size.height as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+        Rectangle::new(Point::new(x, y), Size::new(width, height))
+    }
+
+    /// Returns true if every point of `rect` is in the region.
+    fn covers(region: &DirtyRegion, rect: &Rectangle) -> bool {
+        use embedded_graphics::prelude::PointsIter;
+        rect.points().all(|point| region.rects().iter().any(|dirty| dirty.contains(point)))
+    }
+
+    #[test]
+    fn merges_overlapping_rects() {
+        let mut region = DirtyRegion::new(100, 100);
+        region.add(&rect(0, 0, 10, 10));
+        region.add(&rect(5, 5, 10, 10));
+        assert_eq!(region.rects(), &[rect(0, 0, 15, 15)]);
+        // Merging may make the rectangle overlap another one.
+        region.add(&rect(20, 0, 5, 5));
+        region.add(&rect(10, 0, 12, 2));
+        assert_eq!(region.rects(), &[rect(0, 0, 25, 15)]);
+    }
+
+    #[test]
+    fn keeps_disjoint_rects() {
+        let mut region = DirtyRegion::new(100, 100);
+        region.add(&rect(0, 0, 10, 10));
+        region.add(&rect(10, 0, 10, 10));
+        assert_eq!(region.rects().len(), 2);
+        assert_eq!(region.bounding_box(), Some(rect(0, 0, 20, 10)));
+    }
+
+    #[test]
+    fn covers_all_rects_over_the_limit() {
+        let mut region = DirtyRegion::new(100, 100).max_rects(3);
+        let rects = [
+            rect(0, 0, 5, 5),
+            rect(90, 0, 10, 5),
+            rect(0, 90, 5, 10),
+            rect(50, 50, 3, 3),
+            rect(95, 95, 5, 5),
+            rect(20, 70, 4, 4),
+        ];
+        for (count, added) in rects.iter().enumerate() {
+            region.add(added);
+            assert!(region.rects().len() <= 3);
+            for added in &rects[..=count] {
+                assert!(covers(&region, added), "{:?} is not covered by {:?}", added, region.rects());
+            }
+        }
+    }
+
+    #[test]
+    fn merges_cheapest_pair() {
+        let mut region = DirtyRegion::new(100, 100).max_rects(2);
+        region.add(&rect(0, 0, 10, 10));
+        region.add(&rect(50, 50, 10, 10));
+        region.add(&rect(12, 0, 10, 10));
+        assert!(region.rects().contains(&rect(0, 0, 22, 10)), "{:?}", region.rects());
+        assert!(region.rects().contains(&rect(50, 50, 10, 10)), "{:?}", region.rects());
+    }
+
+    #[test]
+    fn clips_to_bounds() {
+        let mut region = DirtyRegion::new(100, 50);
+        region.add(&rect(-10, -10, 20, 20));
+        region.add(&rect(90, 40, 20, 20));
+        region.add(&rect(200, 0, 10, 10));
+        region.add(&rect(0, 0, 0, 10));
+        assert_eq!(region.rects(), &[rect(0, 0, 10, 10), rect(90, 40, 10, 10)]);
+        region.add_all();
+        assert_eq!(region.take(), [rect(0, 0, 100, 50)]);
+        assert!(region.is_empty());
+    }
+}
@@ -0,0 +1,395 @@
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "std")]
+use embedded_graphics::prelude::{Point, Size};
+#[cfg(feature = "std")]
+use embedded_graphics::primitives::Rectangle;
+
+#[cfg(feature = "std")]
+use crate::dirty::envelope;
+#[cfg(feature = "std")]
+use crate::{ColorRgb888, DrawPrimitives};
+use crate::{EpdMode, LgfxError, LgfxGuard};
+
+/// Guard which restores the previous EPD mode when dropped.
+pub struct EpdModeGuard<'g, 'a> {
+    guard: &'g mut LgfxGuard<'a>,
+    previous: EpdMode,
+}
+
+impl<'g, 'a> Deref for EpdModeGuard<'g, 'a> {
+    type Target = LgfxGuard<'a>;
+    fn deref(&self) -> &Self::Target {
+        self.guard
+    }
+}
+impl<'g, 'a> DerefMut for EpdModeGuard<'g, 'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.guard
+    }
+}
+impl<'g, 'a> Drop for EpdModeGuard<'g, 'a> {
+    fn drop(&mut self) {
+        self.guard.set_epd_mode(self.previous);
+    }
+}
+
+impl<'a> LgfxGuard<'a> {
+    /// Sets the EPD mode until the returned guard is dropped.
+    pub fn scoped_epd_mode<'g>(&'g mut self, mode: EpdMode) -> Result<EpdModeGuard<'g, 'a>, LgfxError> {
+        let previous = self.get_epd_mode()?;
+        self.set_epd_mode(mode);
+        Ok(EpdModeGuard { guard: self, previous })
+    }
+    /// Calls `f` with the EPD mode set to `mode`, and then restores the previous mode.
+    /// e.g. `guard.with_epd_mode(EpdMode::Fast, |g| g.fill_rect(0, 0, 10, 10, color))`
+    pub fn with_epd_mode<R>(&mut self, mode: EpdMode, f: impl FnOnce(&mut LgfxGuard<'a>) -> R) -> Result<R, LgfxError> {
+        let mut scoped = self.scoped_epd_mode(mode)?;
+        Ok(f(&mut scoped))
+    }
+}
+
+/// Thresholds of `EpdModePolicy`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EpdModePolicyConfig {
+    /// Updates within this interval from the previous one are regarded as an animation.
+    pub animation_interval: Duration,
+    /// Updates smaller than this ratio of the screen area use `EpdMode::Fast`.
+    pub small_area_ratio: f32,
+    /// Updates larger than this ratio of the screen area use `EpdMode::Quality`.
+    pub large_area_ratio: f32,
+}
+
+#[cfg(feature = "std")]
+impl Default for EpdModePolicyConfig {
+    fn default() -> Self {
+        Self {
+            animation_interval: Duration::from_millis(500),
+            small_area_ratio: 0.1,
+            large_area_ratio: 0.5,
+        }
+    }
+}
+
+/// Selects the EPD mode from the area and the frequency of the updates.
+///
+/// * Frequent updates use `EpdMode::Fastest`.
+/// * Small updates use `EpdMode::Fast`.
+/// * Large updates use `EpdMode::Quality`.
+/// * Others use `EpdMode::Text`.
+#[cfg(feature = "std")]
+pub struct EpdModePolicy {
+    config: EpdModePolicyConfig,
+    screen_area: u64,
+    last_update: Option<Instant>,
+}
+
+#[cfg(feature = "std")]
+impl EpdModePolicy {
+    pub fn new(screen_width: u32, screen_height: u32, config: EpdModePolicyConfig) -> Self {
+        Self {
+            config,
+            screen_area: u64::from(screen_width) * u64::from(screen_height),
+            last_update: None,
+        }
+    }
+    /// Returns the mode for the update of `rect` at `now`, and records the update.
+    pub fn select(&mut self, rect: &Rectangle, now: Instant) -> EpdMode {
+        let is_animation = self
+            .last_update
+            .is_some_and(|last| now.saturating_duration_since(last) < self.config.animation_interval);
+        self.last_update = Some(now);
+        // In u64 since the rectangle is not clipped to the screen.
+        let area = u64::from(rect.size.width) * u64::from(rect.size.height);
+        let ratio = area as f32 / self.screen_area.max(1) as f32;
+        if is_animation {
+            EpdMode::Fastest
+        } else if ratio < self.config.small_area_ratio {
+            EpdMode::Fast
+        } else if ratio > self.config.large_area_ratio {
+            EpdMode::Quality
+        } else {
+            EpdMode::Text
+        }
+    }
+    /// Selects the mode for the update of `rect` and sets it to the display.
+    pub fn apply(&mut self, guard: &mut LgfxGuard, rect: &Rectangle, now: Instant) -> EpdMode {
+        let mode = self.select(rect, now);
+        guard.set_epd_mode(mode);
+        mode
+    }
+}
+
+/// Thresholds of `GhostingScheduler`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GhostingSchedulerConfig {
+    /// Number of the columns and rows to divide the screen into the regions.
+    pub columns: u32,
+    pub rows: u32,
+    /// Number of partial updates of a region to trigger the refresh of the region.
+    pub max_partial_updates: u32,
+    /// Interval to trigger the refresh of the whole screen regardless of the updates.
+    pub max_interval: Option<Duration>,
+}
+
+#[cfg(feature = "std")]
+impl Default for GhostingSchedulerConfig {
+    fn default() -> Self {
+        Self {
+            columns: 4,
+            rows: 4,
+            max_partial_updates: 20,
+            max_interval: Some(Duration::from_secs(30 * 60)),
+        }
+    }
+}
+
+/// Schedules the quality refresh of the EPD to remove the ghosting caused by the partial updates with fast modes.
+///
+/// Record each partial update with `record_update`, and call `refresh_if_needed` periodically.
+#[cfg(feature = "std")]
+pub struct GhostingScheduler {
+    config: GhostingSchedulerConfig,
+    width: u32,
+    height: u32,
+    counts: Vec<u32>,
+    last_full_refresh: Instant,
+}
+
+#[cfg(feature = "std")]
+impl GhostingScheduler {
+    /// Creates a scheduler for the screen which is fully refreshed at `now`.
+    pub fn new(screen_width: u32, screen_height: u32, config: GhostingSchedulerConfig, now: Instant) -> Self {
+        let config = GhostingSchedulerConfig {
+            columns: config.columns.clamp(1, screen_width.max(1)),
+            rows: config.rows.clamp(1, screen_height.max(1)),
+            ..config
+        };
+        Self {
+            config,
+            width: screen_width,
+            height: screen_height,
+            counts: vec![0; (config.columns * config.rows) as usize],
+            last_full_refresh: now,
+        }
+    }
+
+    fn screen(&self) -> Rectangle {
+        Rectangle::new(Point::zero(), Size::new(self.width, self.height))
+    }
+    fn cell(&self, column: u32, row: u32) -> Rectangle {
+        let left = self.width * column / self.config.columns;
+        let top = self.height * row / self.config.rows;
+        let right = self.width * (column + 1) / self.config.columns;
+        let bottom = self.height * (row + 1) / self.config.rows;
+        Rectangle::new(Point::new(left as i32, top as i32), Size::new(right - left, bottom - top))
+    }
+    fn cells(&self) -> impl Iterator<Item = (usize, Rectangle)> + '_ {
+        (0..self.config.rows)
+            .flat_map(move |row| (0..self.config.columns).map(move |column| (column, row)))
+            .enumerate()
+            .map(move |(index, (column, row))| (index, self.cell(column, row)))
+    }
+
+    /// Records the partial update of `rect`.
+    pub fn record_update(&mut self, rect: &Rectangle) {
+        let overlapped: Vec<usize> = self
+            .cells()
+            .filter(|(_, cell)| !cell.intersection(rect).is_zero_sized())
+            .map(|(index, _)| index)
+            .collect();
+        for index in overlapped {
+            self.counts[index] += 1;
+        }
+    }
+
+    /// Returns the region to be refreshed, or `None` if no refresh is needed.
+    pub fn pending_refresh(&self, now: Instant) -> Option<Rectangle> {
+        let expired = self
+            .config
+            .max_interval
+            .is_some_and(|interval| now.saturating_duration_since(self.last_full_refresh) >= interval);
+        if expired {
+            return Some(self.screen());
+        }
+        self.cells()
+            .filter(|(index, _)| self.counts[*index] >= self.config.max_partial_updates)
+            .map(|(_, cell)| cell)
+            .reduce(|region, cell| envelope(&region, &cell))
+    }
+
+    /// Refreshes the region returned by `pending_refresh` if any, and returns true if refreshed.
+    ///
+    /// The region is cleared and displayed with `EpdMode::Quality` to flash the panel,
+    /// and then `redraw` is called to draw the contents of the region again.
+    /// The EPD mode is restored after the refresh.
+    pub fn refresh_if_needed(
+        &mut self,
+        guard: &mut LgfxGuard,
+        now: Instant,
+        redraw: impl FnOnce(&mut LgfxGuard, &Rectangle),
+    ) -> Result<bool, LgfxError> {
+        let region = match self.pending_refresh(now) {
+            Some(region) => region,
+            None => return Ok(false),
+        };
+        guard.with_epd_mode(EpdMode::Quality, |guard| {
+            guard.fill_rect(
+                region.top_left.x,
+                region.top_left.y,
+                region.size.width as i32,
+                region.size.height as i32,
+                ColorRgb888::new(0xffffff),
+            );
+            guard.display_region(&region);
+            guard.wait_display();
+            redraw(guard, &region);
+            guard.display_region(&region);
+            guard.wait_display();
+        })?;
+        self.mark_refreshed(&region, now);
+        Ok(true)
+    }
+
+    /// Resets the counts of the regions refreshed by the application.
+    pub fn mark_refreshed(&mut self, region: &Rectangle, now: Instant) {
+        let refreshed: Vec<usize> = self
+            .cells()
+            .filter(|(_, cell)| region.intersection(cell) == *cell)
+            .map(|(index, _)| index)
+            .collect();
+        for index in refreshed {
+            self.counts[index] = 0;
+        }
+        if *region == self.screen() {
+            self.last_full_refresh = now;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+        Rectangle::new(Point::new(x, y), Size::new(width, height))
+    }
+
+    fn policy() -> EpdModePolicy {
+        // 10% and 50% of 100x100.
+        EpdModePolicy::new(100, 100, EpdModePolicyConfig::default())
+    }
+
+    #[test]
+    fn selects_fast_below_small_ratio() {
+        let now = Instant::now();
+        assert_eq!(policy().select(&rect(0, 0, 10, 99), now), EpdMode::Fast);
+        assert_eq!(policy().select(&rect(0, 0, 10, 100), now), EpdMode::Text);
+    }
+
+    #[test]
+    fn selects_quality_above_large_ratio() {
+        let now = Instant::now();
+        assert_eq!(policy().select(&rect(0, 0, 50, 100), now), EpdMode::Text);
+        assert_eq!(policy().select(&rect(0, 0, 51, 100), now), EpdMode::Quality);
+    }
+
+    #[test]
+    fn selects_fastest_for_animation() {
+        let start = Instant::now();
+        let mut policy = policy();
+        let large = rect(0, 0, 100, 100);
+        assert_eq!(policy.select(&large, start), EpdMode::Quality);
+        let next = start + Duration::from_millis(499);
+        assert_eq!(policy.select(&large, next), EpdMode::Fastest);
+        // The interval is measured from the previous update.
+        let after_pause = next + Duration::from_millis(500);
+        assert_eq!(policy.select(&large, after_pause), EpdMode::Quality);
+    }
+
+    #[test]
+    fn handles_empty_screen() {
+        let now = Instant::now();
+        let mut policy = EpdModePolicy::new(0, 0, EpdModePolicyConfig::default());
+        assert_eq!(policy.select(&rect(0, 0, 0, 0), now), EpdMode::Fast);
+        let mut policy = EpdModePolicy::new(0, 100, EpdModePolicyConfig::default());
+        assert_eq!(policy.select(&rect(0, 0, 10, 10), now), EpdMode::Quality);
+    }
+
+    #[test]
+    fn handles_huge_sizes() {
+        let now = Instant::now();
+        let mut policy = EpdModePolicy::new(u32::MAX, u32::MAX, EpdModePolicyConfig::default());
+        assert_eq!(policy.select(&rect(0, 0, 10, 10), now), EpdMode::Fast);
+        // An unclipped rectangle larger than the screen.
+        let mut policy = EpdModePolicy::new(100, 100, EpdModePolicyConfig::default());
+        assert_eq!(policy.select(&rect(-10, -10, u32::MAX, u32::MAX), now), EpdMode::Quality);
+    }
+
+    /// 4x2 cells of 240x270 on 960x540, refreshed after 3 updates or 10 seconds.
+    fn scheduler(now: Instant) -> GhostingScheduler {
+        let config = GhostingSchedulerConfig {
+            columns: 4,
+            rows: 2,
+            max_partial_updates: 3,
+            max_interval: Some(Duration::from_secs(10)),
+        };
+        GhostingScheduler::new(960, 540, config, now)
+    }
+
+    #[test]
+    fn counts_every_overlapped_cell() {
+        let now = Instant::now();
+        let mut scheduler = scheduler(now);
+        for _ in 0..3 {
+            // Overlaps the first two cells of the top row.
+            scheduler.record_update(&rect(200, 0, 100, 10));
+        }
+        assert_eq!(scheduler.pending_refresh(now), Some(rect(0, 0, 480, 270)));
+    }
+
+    #[test]
+    fn refreshes_at_threshold() {
+        let now = Instant::now();
+        let mut scheduler = scheduler(now);
+        scheduler.record_update(&rect(300, 300, 10, 10));
+        scheduler.record_update(&rect(300, 300, 10, 10));
+        assert_eq!(scheduler.pending_refresh(now), None);
+        scheduler.record_update(&rect(250, 280, 10, 10));
+        assert_eq!(scheduler.pending_refresh(now), Some(rect(240, 270, 240, 270)));
+        scheduler.mark_refreshed(&rect(240, 270, 240, 270), now);
+        assert_eq!(scheduler.pending_refresh(now), None);
+    }
+
+    #[test]
+    fn refreshes_whole_screen_after_interval() {
+        let start = Instant::now();
+        let mut scheduler = scheduler(start);
+        assert_eq!(scheduler.pending_refresh(start + Duration::from_secs(9)), None);
+        let expired = start + Duration::from_secs(10);
+        assert_eq!(scheduler.pending_refresh(expired), Some(rect(0, 0, 960, 540)));
+        // A partial refresh does not restart the interval.
+        scheduler.mark_refreshed(&rect(0, 0, 240, 270), expired);
+        assert_eq!(scheduler.pending_refresh(expired), Some(rect(0, 0, 960, 540)));
+        scheduler.mark_refreshed(&rect(0, 0, 960, 540), expired);
+        assert_eq!(scheduler.pending_refresh(expired + Duration::from_secs(9)), None);
+    }
+
+    #[test]
+    fn partial_cell_refresh_keeps_counts() {
+        let now = Instant::now();
+        let mut scheduler = scheduler(now);
+        for _ in 0..3 {
+            scheduler.record_update(&rect(0, 0, 10, 10));
+        }
+        // Covers only the half of the first cell.
+        scheduler.mark_refreshed(&rect(0, 0, 120, 270), now);
+        assert_eq!(scheduler.pending_refresh(now), Some(rect(0, 0, 240, 270)));
+        scheduler.mark_refreshed(&rect(0, 0, 240, 270), now);
+        assert_eq!(scheduler.pending_refresh(now), None);
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::button::{ButtonEvent, KeyButtonMap};
+use crate::{Gfx, LgfxError, Screen, TouchPoint};
+
+/// Events yielded by `EventLoop`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The application was requested to quit, e.g. the last simulator window was closed.
+    /// Emitted once to the event loop of each display. Never emitted on ESP-IDF.
+    Quit,
+    /// The simulator window of this display was closed. Never emitted on ESP-IDF.
+    Close,
+    /// A key was pressed. `keycode` is the SDL keycode. Never emitted on ESP-IDF.
+    KeyDown { keycode: i32 },
+    /// A key was released. `keycode` is the SDL keycode. Never emitted on ESP-IDF.
+    KeyUp { keycode: i32 },
+    /// A logical button was pressed or released.
+    /// Emitted for the keys mapped by `KeyButtonMap` on Linux, and for `GpioButtons` on ESP-IDF.
+    Button(ButtonEvent),
+    /// The screen was touched, or the mouse button was pressed on the simulator window.
+    TouchDown(TouchPoint),
+    TouchMove(TouchPoint),
+    TouchUp(TouchPoint),
+    /// The logical size of the screen was changed, e.g. by the rotation.
+    Resize { width: i32, height: i32 },
+    /// The frame interval has elapsed.
+    /// `frame` counts the ticks from zero, and `elapsed` is the time since the previous tick.
+    FrameTick { frame: u64, elapsed: Duration },
+}
+
+/// Cross-platform event loop over the display.
+///
+/// On Linux, SDL events are pumped on each poll, so the loop must be driven from the main thread.
+/// Touch events are detected by polling the touch API of the display on both targets.
+pub struct EventLoop<'a> {
+    gfx: &'a Gfx,
+    // Identifies the events of the window of this display.
+    #[cfg(target_os = "linux")]
+    target: crate::lgfx_target_t,
+    frame_interval: Option<Duration>,
+    frame: u64,
+    last_tick: Instant,
+    touch: Option<TouchPoint>,
+    size: (i32, i32),
+    pending: VecDeque<Event>,
+    // Only the simulator has the keyboard.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    key_buttons: Option<KeyButtonMap>,
+    #[cfg(target_os = "espidf")]
+    gpio_buttons: Option<crate::button::GpioButtons>,
+}
+
+impl<'a> EventLoop<'a> {
+    /// Creates an event loop without frame ticks.
+    pub fn new(gfx: &'a Gfx) -> Result<Self, LgfxError> {
+        let shared = gfx.as_shared();
+        let guard = shared.lock()?;
+        let size = guard.size();
+        #[cfg(target_os = "linux")]
+        let target = crate::LgfxTarget::target(&guard);
+        drop(guard);
+        Ok(Self {
+            gfx,
+            #[cfg(target_os = "linux")]
+            target,
+            frame_interval: None,
+            frame: 0,
+            last_tick: Instant::now(),
+            touch: None,
+            size,
+            pending: VecDeque::new(),
+            key_buttons: None,
+            #[cfg(target_os = "espidf")]
+            gpio_buttons: None,
+        })
+    }
+    /// Emits `Event::FrameTick` every `frame_interval`.
+    pub fn frame_interval(mut self, frame_interval: Duration) -> Self {
+        self.frame_interval = Some(frame_interval);
+        self.last_tick = Instant::now();
+        self
+    }
+    /// Emits `Event::Button` for the keys of the simulator in addition to the key events.
+    pub fn key_buttons(mut self, map: KeyButtonMap) -> Self {
+        self.key_buttons = Some(map);
+        self
+    }
+    /// Emits `Event::Button` for the buttons connected to GPIOs.
+    #[cfg(target_os = "espidf")]
+    pub fn gpio_buttons(mut self, buttons: crate::button::GpioButtons) -> Self {
+        self.gpio_buttons = Some(buttons);
+        self
+    }
+
+    /// Returns the next event if available without blocking.
+    pub fn poll(&mut self) -> Option<Event> {
+        if self.pending.is_empty() {
+            self.collect_events();
+        }
+        self.pending.pop_front()
+    }
+    /// Blocks until the next event is available.
+    pub fn next_event(&mut self) -> Event {
+        const POLL_INTERVAL: Duration = Duration::from_millis(5);
+        loop {
+            if let Some(event) = self.poll() {
+                return event;
+            }
+            let wait = match self.frame_interval {
+                Some(interval) => (self.last_tick + interval)
+                    .saturating_duration_since(Instant::now())
+                    .min(POLL_INTERVAL),
+                None => POLL_INTERVAL,
+            };
+            std::thread::sleep(wait);
+        }
+    }
+
+    fn collect_events(&mut self) {
+        #[cfg(target_os = "linux")]
+        self.collect_sdl_events();
+        #[cfg(target_os = "espidf")]
+        if let Some(buttons) = self.gpio_buttons.as_mut() {
+            let pending = &mut self.pending;
+            buttons.poll(|event| pending.push_back(Event::Button(event)));
+        }
+
+        // The touch and the size are polled next time if the display is being drawn by others.
+        let polled = self.gfx.as_shared().try_lock().map(|mut guard| (guard.get_touch(), guard.size()));
+        if let Ok((touch, size)) = polled {
+            match (self.touch, touch) {
+                (None, Some(current)) => self.pending.push_back(Event::TouchDown(current)),
+                (Some(last), Some(current)) if last != current => self.pending.push_back(Event::TouchMove(current)),
+                (Some(last), None) => self.pending.push_back(Event::TouchUp(last)),
+                _ => {}
+            }
+            self.touch = touch;
+            if size != self.size {
+                self.size = size;
+                self.pending.push_back(Event::Resize { width: size.0, height: size.1 });
+            }
+        }
+
+        if let Some(interval) = self.frame_interval {
+            let now = Instant::now();
+            let elapsed = now.duration_since(self.last_tick);
+            if elapsed >= interval {
+                self.pending.push_back(Event::FrameTick { frame: self.frame, elapsed });
+                self.frame += 1;
+                self.last_tick = now;
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[allow(non_upper_case_globals)]
+    fn collect_sdl_events(&mut self) {
+        use crate::{
+            event_t, event_type_event_close, event_type_event_key_down, event_type_event_key_up, event_type_event_quit,
+            lgfx_c_panel_sdl_poll_event,
+        };
+
+        Gfx::handle_sdl_event();
+        let mut raw = event_t { type_: 0, keycode: 0, target: core::ptr::null_mut() };
+        while unsafe { lgfx_c_panel_sdl_poll_event(self.target, &mut raw) } {
+            let event = match raw.type_ {
+                event_type_event_quit => Event::Quit,
+                event_type_event_close => Event::Close,
+                event_type_event_key_down => Event::KeyDown { keycode: raw.keycode },
+                event_type_event_key_up => Event::KeyUp { keycode: raw.keycode },
+                _ => continue,
+            };
+            self.pending.push_back(event);
+            if let Some(button) = self.key_buttons.as_ref().and_then(|map| map.translate(&event)) {
+                self.pending.push_back(Event::Button(button));
+            }
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Frame pacing and frame time statistics.
+
+use core::time::Duration;
+
+use crate::{fonts, textdatum_top_left, ColorRgb888, DrawString, FontManupulation};
+
+/// Interval to update the FPS shown by `FrameClock::fps`.
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+/// Source of the time for `FrameClock`.
+pub trait Clock {
+    /// Returns the monotonic time since an arbitrary origin.
+    fn now(&self) -> Duration;
+    fn sleep(&self, duration: Duration);
+}
+
+/// Clock of `std::time::Instant`.
+/// On ESP-IDF, the sleep is rounded to the FreeRTOS tick, which is 10 ms by default.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+pub struct StdClock {
+    origin: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> Self {
+        Self { origin: std::time::Instant::now() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// Statistics of the frame times, which are the intervals between `FrameClock::tick`s.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub frames: u32,
+    pub min: Duration,
+    pub max: Duration,
+    pub total: Duration,
+    /// Number of the frame periods skipped by the frames slower than the target.
+    pub dropped: u32,
+}
+
+impl FrameStats {
+    pub fn average(&self) -> Duration {
+        if self.frames == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.frames
+        }
+    }
+    /// Average frames per second.
+    pub fn fps(&self) -> f32 {
+        if self.total.is_zero() {
+            0.0
+        } else {
+            self.frames as f32 / self.total.as_secs_f32()
+        }
+    }
+
+    fn record(&mut self, frame_time: Duration, period: Option<Duration>) {
+        if self.frames == 0 {
+            self.min = frame_time;
+            self.max = frame_time;
+        } else {
+            self.min = self.min.min(frame_time);
+            self.max = self.max.max(frame_time);
+        }
+        self.frames += 1;
+        self.total += frame_time;
+        if let Some(period) = period {
+            if frame_time > period {
+                // e.g. a frame of 1.5 periods skips one period.
+                let periods = frame_time.as_nanos().div_ceil(period.as_nanos());
+                self.dropped += (periods - 1) as u32;
+            }
+        }
+    }
+}
+
+/// Paces the frames to the target FPS and measures the frame times.
+///
+/// ```ignore
+/// let mut clock = FrameClock::new(30);
+/// loop {
+///     let mut guard = shared.lock()?;
+///     draw_scene(&mut guard);
+///     clock.draw_fps(&mut guard, 0, 0);
+///     drop(guard);
+///     let elapsed = clock.tick();
+/// }
+/// ```
+pub struct FrameClock<K: Clock> {
+    clock: K,
+    period: Option<Duration>,
+    deadline: Duration,
+    last_tick: Option<Duration>,
+    stats: FrameStats,
+    window: FrameStats,
+    fps: f32,
+}
+
+#[cfg(feature = "std")]
+impl FrameClock<StdClock> {
+    /// Creates a clock targeting `target_fps` frames per second. 0 disables the pacing.
+    pub fn new(target_fps: u32) -> Self {
+        Self::with_clock(StdClock::default(), target_fps)
+    }
+}
+
+impl<K: Clock> FrameClock<K> {
+    pub fn with_clock(clock: K, target_fps: u32) -> Self {
+        Self {
+            clock,
+            period: (target_fps > 0).then(|| Duration::from_secs(1) / target_fps),
+            deadline: Duration::ZERO,
+            last_tick: None,
+            stats: FrameStats::default(),
+            window: FrameStats::default(),
+            fps: 0.0,
+        }
+    }
+
+    /// Target interval of the frames, or `None` if the pacing is disabled.
+    pub fn period(&self) -> Option<Duration> {
+        self.period
+    }
+
+    /// Ends the frame. Sleeps until the next frame period, and returns the time since the previous tick.
+    /// The first tick only starts the measurement and returns zero.
+    ///
+    /// If the frame overran the period, the next frame starts immediately
+    /// and the pacing restarts from it, instead of rushing to catch up.
+    pub fn tick(&mut self) -> Duration {
+        let Some(last_tick) = self.last_tick else {
+            let now = self.clock.now();
+            self.last_tick = Some(now);
+            self.deadline = now + self.period.unwrap_or_default();
+            return Duration::ZERO;
+        };
+        if let Some(period) = self.period {
+            let now = self.clock.now();
+            if now < self.deadline {
+                self.clock.sleep(self.deadline - now);
+                self.deadline += period;
+            } else {
+                self.deadline = now + period;
+            }
+        }
+        let now = self.clock.now();
+        let frame_time = now.saturating_sub(last_tick);
+        self.last_tick = Some(now);
+
+        self.stats.record(frame_time, self.period);
+        self.window.record(frame_time, self.period);
+        if self.window.total >= FPS_WINDOW {
+            self.fps = self.window.fps();
+            self.window = FrameStats::default();
+        }
+        frame_time
+    }
+
+    /// Statistics since the creation or the last `reset_stats`.
+    pub fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+    /// Returns the statistics and starts a new measurement.
+    pub fn reset_stats(&mut self) -> FrameStats {
+        core::mem::take(&mut self.stats)
+    }
+    /// FPS over the last second. Zero until the first second elapses.
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    /// Draws the FPS at (x, y) with `Font0`, white on black.
+    /// The font of the target is restored after drawing. Returns the size of the drawn text.
+    pub fn draw_fps<T>(&self, target: &mut T, x: i32, y: i32) -> (i32, i32)
+    where
+        T: DrawString<ColorRgb888> + FontManupulation,
+    {
+        let font = target.get_font();
+        if target.set_font(fonts::Font0).is_err() {
+            return (0, 0);
+        }
+        let text = alloc::format!("{:.1} FPS", self.fps);
+        let size = target.draw_string(
+            &text,
+            x,
+            y,
+            ColorRgb888::new(0xffffff),
+            Some(ColorRgb888::new(0x000000)),
+            1.0,
+            1.0,
+            textdatum_top_left,
+        );
+        if let Ok(font) = font {
+            target.set_font(font).ok();
+        }
+        size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    /// Clock which advances only by `sleep` and `work`.
+    #[derive(Clone, Default)]
+    struct FakeClock {
+        now: Rc<Cell<Duration>>,
+        slept: Rc<Cell<Duration>>,
+    }
+    impl FakeClock {
+        fn work(&self, duration: Duration) {
+            self.now.set(self.now.get() + duration);
+        }
+    }
+    impl Clock for FakeClock {
+        fn now(&self) -> Duration {
+            self.now.get()
+        }
+        fn sleep(&self, duration: Duration) {
+            self.slept.set(self.slept.get() + duration);
+            self.work(duration);
+        }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn sleeps_until_next_period() {
+        let clock = FakeClock::default();
+        let mut frames = FrameClock::with_clock(clock.clone(), 50);
+        frames.tick();
+        for _ in 0..3 {
+            clock.work(ms(5));
+            assert_eq!(frames.tick(), ms(20));
+        }
+        assert_eq!(clock.slept.get(), ms(45));
+        let stats = frames.stats();
+        assert_eq!((stats.frames, stats.min, stats.max, stats.dropped), (3, ms(20), ms(20), 0));
+    }
+
+    #[test]
+    fn counts_dropped_frames() {
+        let clock = FakeClock::default();
+        let mut frames = FrameClock::with_clock(clock.clone(), 50);
+        frames.tick();
+        clock.work(ms(30));
+        assert_eq!(frames.tick(), ms(30));
+        clock.work(ms(45));
+        assert_eq!(frames.tick(), ms(45));
+        assert_eq!(frames.stats().dropped, 1 + 2);
+    }
+
+    #[test]
+    fn restarts_pacing_after_overrun() {
+        let clock = FakeClock::default();
+        let mut frames = FrameClock::with_clock(clock.clone(), 50);
+        frames.tick();
+        clock.work(ms(70));
+        frames.tick();
+        // The next frame gets the full period instead of catching up.
+        clock.work(ms(5));
+        assert_eq!(frames.tick(), ms(20));
+        // Also after overrunning less than a period.
+        clock.work(ms(25));
+        assert_eq!(frames.tick(), ms(25));
+        clock.work(ms(5));
+        assert_eq!(frames.tick(), ms(20));
+        assert_eq!(clock.now(), ms(135));
+    }
+
+    #[test]
+    fn keeps_phase_when_on_time() {
+        let clock = FakeClock::default();
+        let mut frames = FrameClock::with_clock(clock.clone(), 50);
+        frames.tick();
+        clock.work(ms(20));
+        frames.tick();
+        clock.work(ms(10));
+        assert_eq!(frames.tick(), ms(20));
+        assert_eq!(clock.now(), ms(40));
+    }
+
+    #[test]
+    fn reports_min_avg_max() {
+        let clock = FakeClock::default();
+        let mut frames = FrameClock::with_clock(clock.clone(), 0);
+        frames.tick();
+        for work in [10, 30, 20] {
+            clock.work(ms(work));
+            frames.tick();
+        }
+        let stats = frames.reset_stats();
+        assert_eq!((stats.min, stats.average(), stats.max), (ms(10), ms(20), ms(30)));
+        assert_eq!(stats.dropped, 0);
+        assert_eq!(clock.slept.get(), Duration::ZERO);
+        assert_eq!(frames.stats().frames, 0);
+    }
+
+    #[test]
+    fn updates_fps_every_second() {
+        let clock = FakeClock::default();
+        let mut frames = FrameClock::with_clock(clock.clone(), 25);
+        frames.tick();
+        for _ in 0..24 {
+            frames.tick();
+        }
+        assert_eq!(frames.fps(), 0.0);
+        frames.tick();
+        assert!((frames.fps() - 25.0).abs() < 0.01);
+    }
+}
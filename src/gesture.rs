@@ -0,0 +1,314 @@
+use core::time::Duration;
+
+use crate::TouchPoint;
+
+/// A touch state sampled at `timestamp`.
+/// `point` is `None` if the screen is not touched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TouchSample {
+    pub timestamp: Duration,
+    pub point: Option<TouchPoint>,
+}
+
+impl TouchSample {
+    pub fn new(timestamp: Duration, point: Option<TouchPoint>) -> Self {
+        Self { timestamp, point }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwipeDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    Tap { x: i32, y: i32 },
+    /// Emitted at the second tap. The first tap has already been reported as `Tap`.
+    DoubleTap { x: i32, y: i32 },
+    LongPress { x: i32, y: i32 },
+    /// Emitted at release if the touch point moved fast enough.
+    /// `velocity` is in pixels per second.
+    Swipe { direction: SwipeDirection, velocity: f32 },
+    /// Emitted for every sample while the touch point is moving.
+    /// `dx` and `dy` are the movement from the previous sample.
+    Drag { x: i32, y: i32, dx: i32, dy: i32 },
+}
+
+/// Thresholds of the gesture recognition.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GestureConfig {
+    /// Maximum movement in pixels to regard the touch as stationary.
+    pub slop: i32,
+    /// Maximum duration of a touch to be a tap.
+    pub tap_timeout: Duration,
+    /// Maximum interval between the releases of two taps to be a double tap.
+    pub double_tap_timeout: Duration,
+    /// Duration of a stationary touch to be a long press.
+    pub long_press_timeout: Duration,
+    /// Minimum distance in pixels of a swipe.
+    pub swipe_min_distance: i32,
+    /// Minimum velocity in pixels per second of a swipe.
+    pub swipe_min_velocity: f32,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            slop: 10,
+            tap_timeout: Duration::from_millis(300),
+            double_tap_timeout: Duration::from_millis(300),
+            long_press_timeout: Duration::from_millis(600),
+            swipe_min_distance: 40,
+            swipe_min_velocity: 200.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum State {
+    Idle,
+    Pressed {
+        start: TouchPoint,
+        start_time: Duration,
+        long_pressed: bool,
+    },
+    Dragging {
+        start: TouchPoint,
+        start_time: Duration,
+        last: TouchPoint,
+    },
+}
+
+/// Recognizes gestures from touch samples.
+///
+/// Call `update` periodically with the touch state, including the samples without touch,
+/// since long presses and releases are detected from the sequence of samples.
+pub struct GestureRecognizer {
+    config: GestureConfig,
+    state: State,
+    last_tap: Option<(TouchPoint, Duration)>,
+}
+
+impl GestureRecognizer {
+    pub fn new(config: GestureConfig) -> Self {
+        Self {
+            config,
+            state: State::Idle,
+            last_tap: None,
+        }
+    }
+    pub fn config(&self) -> &GestureConfig {
+        &self.config
+    }
+    /// Discards the touch in progress.
+    pub fn reset(&mut self) {
+        self.state = State::Idle;
+        self.last_tap = None;
+    }
+
+    pub fn update(&mut self, sample: TouchSample) -> Option<Gesture> {
+        let now = sample.timestamp;
+        match (self.state, sample.point) {
+            (State::Idle, None) => None,
+            (State::Idle, Some(point)) => {
+                self.state = State::Pressed {
+                    start: point,
+                    start_time: now,
+                    long_pressed: false,
+                };
+                None
+            }
+            (State::Pressed { start, start_time, long_pressed }, Some(point)) => {
+                if !self.is_near(&start, &point) {
+                    self.state = State::Dragging { start, start_time, last: start };
+                    self.drag(point)
+                } else if !long_pressed && now.saturating_sub(start_time) >= self.config.long_press_timeout {
+                    self.state = State::Pressed { start, start_time, long_pressed: true };
+                    self.last_tap = None;
+                    Some(Gesture::LongPress { x: start.x, y: start.y })
+                } else {
+                    None
+                }
+            }
+            (State::Pressed { start, start_time, long_pressed }, None) => {
+                self.state = State::Idle;
+                if long_pressed || now.saturating_sub(start_time) > self.config.tap_timeout {
+                    return None;
+                }
+                match self.last_tap.take() {
+                    Some((last, last_time))
+                        if now.saturating_sub(last_time) <= self.config.double_tap_timeout
+                            && self.is_near(&last, &start) =>
+                    {
+                        Some(Gesture::DoubleTap { x: start.x, y: start.y })
+                    }
+                    _ => {
+                        self.last_tap = Some((start, now));
+                        Some(Gesture::Tap { x: start.x, y: start.y })
+                    }
+                }
+            }
+            (State::Dragging { .. }, Some(point)) => self.drag(point),
+            (State::Dragging { start, start_time, last }, None) => {
+                self.state = State::Idle;
+                self.last_tap = None;
+                self.swipe(&start, &last, now.saturating_sub(start_time))
+            }
+        }
+    }
+
+    fn is_near(&self, a: &TouchPoint, b: &TouchPoint) -> bool {
+        (a.x - b.x).abs() <= self.config.slop && (a.y - b.y).abs() <= self.config.slop
+    }
+    fn drag(&mut self, point: TouchPoint) -> Option<Gesture> {
+        if let State::Dragging { last, .. } = &mut self.state {
+            let (dx, dy) = (point.x - last.x, point.y - last.y);
+            *last = point;
+            Some(Gesture::Drag { x: point.x, y: point.y, dx, dy })
+        } else {
+            None
+        }
+    }
+    fn swipe(&self, start: &TouchPoint, end: &TouchPoint, duration: Duration) -> Option<Gesture> {
+        let (dx, dy) = (end.x - start.x, end.y - start.y);
+        let distance = crate::math::sqrt((dx * dx + dy * dy) as f32);
+        if distance < self.config.swipe_min_distance as f32 {
+            return None;
+        }
+        let seconds = duration.as_secs_f32();
+        let velocity = if seconds > 0.0 { distance / seconds } else { f32::INFINITY };
+        if velocity < self.config.swipe_min_velocity {
+            return None;
+        }
+        let direction = if dx.abs() >= dy.abs() {
+            if dx < 0 { SwipeDirection::Left } else { SwipeDirection::Right }
+        } else if dy < 0 {
+            SwipeDirection::Up
+        } else {
+            SwipeDirection::Down
+        };
+        Some(Gesture::Swipe { direction, velocity })
+    }
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new(GestureConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(ms: u64, x: i32, y: i32) -> TouchSample {
+        TouchSample::new(Duration::from_millis(ms), Some(TouchPoint { x, y, size: 0, id: 0 }))
+    }
+    fn release(ms: u64) -> TouchSample {
+        TouchSample::new(Duration::from_millis(ms), None)
+    }
+    fn run(recognizer: &mut GestureRecognizer, samples: &[TouchSample]) -> Vec<Gesture> {
+        samples.iter().filter_map(|sample| recognizer.update(*sample)).collect()
+    }
+
+    #[test]
+    fn tap() {
+        let mut recognizer = GestureRecognizer::default();
+        let gestures = run(&mut recognizer, &[touch(0, 10, 20), touch(50, 12, 21), release(100)]);
+        assert_eq!(gestures, [Gesture::Tap { x: 10, y: 20 }]);
+    }
+
+    #[test]
+    fn slow_release_is_not_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        let gestures = run(&mut recognizer, &[touch(0, 10, 20), release(400)]);
+        assert!(gestures.is_empty());
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        let gestures = run(
+            &mut recognizer,
+            &[touch(0, 10, 20), release(50), touch(150, 14, 18), release(200)],
+        );
+        assert_eq!(gestures, [Gesture::Tap { x: 10, y: 20 }, Gesture::DoubleTap { x: 14, y: 18 }]);
+    }
+
+    #[test]
+    fn distant_taps_are_not_double_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        let gestures = run(
+            &mut recognizer,
+            &[touch(0, 10, 20), release(50), touch(150, 100, 20), release(200)],
+        );
+        assert_eq!(gestures, [Gesture::Tap { x: 10, y: 20 }, Gesture::Tap { x: 100, y: 20 }]);
+    }
+
+    #[test]
+    fn late_second_tap_is_not_double_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        let gestures = run(
+            &mut recognizer,
+            &[touch(0, 10, 20), release(50), touch(500, 10, 20), release(550)],
+        );
+        assert_eq!(gestures, [Gesture::Tap { x: 10, y: 20 }, Gesture::Tap { x: 10, y: 20 }]);
+    }
+
+    #[test]
+    fn long_press() {
+        let mut recognizer = GestureRecognizer::default();
+        let gestures = run(
+            &mut recognizer,
+            &[touch(0, 10, 20), touch(300, 11, 20), touch(600, 11, 21), touch(900, 11, 21), release(1000)],
+        );
+        assert_eq!(gestures, [Gesture::LongPress { x: 10, y: 20 }]);
+    }
+
+    #[test]
+    fn drag_without_swipe() {
+        let mut recognizer = GestureRecognizer::default();
+        let gestures = run(
+            &mut recognizer,
+            &[touch(0, 0, 0), touch(500, 20, 0), touch(1000, 30, 5), release(1100)],
+        );
+        assert_eq!(
+            gestures,
+            [
+                Gesture::Drag { x: 20, y: 0, dx: 20, dy: 0 },
+                Gesture::Drag { x: 30, y: 5, dx: 10, dy: 5 },
+            ]
+        );
+    }
+
+    #[test]
+    fn swipe() {
+        let mut recognizer = GestureRecognizer::default();
+        let gestures = run(
+            &mut recognizer,
+            &[touch(0, 100, 100), touch(50, 100, 60), touch(100, 100, 0), release(100)],
+        );
+        assert_eq!(
+            gestures,
+            [
+                Gesture::Drag { x: 100, y: 60, dx: 0, dy: -40 },
+                Gesture::Drag { x: 100, y: 0, dx: 0, dy: -60 },
+                Gesture::Swipe { direction: SwipeDirection::Up, velocity: 1000.0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_thresholds() {
+        let mut recognizer = GestureRecognizer::new(GestureConfig {
+            swipe_min_distance: 200,
+            ..GestureConfig::default()
+        });
+        let gestures = run(&mut recognizer, &[touch(0, 0, 0), touch(50, 100, 0), release(60)]);
+        assert_eq!(gestures, [Gesture::Drag { x: 100, y: 0, dx: 100, dy: 0 }]);
+    }
+}
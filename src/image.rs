@@ -0,0 +1,376 @@
+use core::marker::PhantomData;
+
+use alloc::vec;
+use embedded_graphics::{
+    image::ImageDrawable,
+    pixelcolor::Rgb888,
+    prelude::*,
+    primitives::Rectangle,
+};
+
+use crate::{lgfx_c_create_sprite_with_depth, lgfx_c_draw_jpg, lgfx_c_draw_png, lgfx_c_read_rect_rgb888};
+use crate::{lgfx_target_t, textdatum_t, textdatum_top_left, LgfxDisplay, LgfxError, LgfxTarget, Sprite};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+}
+
+/// PNG or JPEG encoded image which can be drawn by embedded-graphics `Image`.
+///
+/// The image is decoded by LovyanGFX into `Rgb888`, and converted into the color type `C`.
+/// Use `with_color` to draw on the targets of the other color types, e.g. `LgfxDisplay<_, Rgb565>`.
+///
+/// Drawing to a `DrawTarget` decodes the visible area into a temporary sprite first, band by band if the memory is short,
+/// and copies the pixels through `fill_contiguous`. This applies to `LgfxDisplay` too, since `Image::draw` cannot
+/// detect the target type. `LgfxDisplay::draw_image` decodes the image directly into the display without the sprite.
+///
+/// `ImageDrawable` cannot report the decoding errors, so a corrupt image is silently not drawn by `Image::draw`.
+/// `try_draw` and `LgfxDisplay::draw_image` report them.
+#[derive(Clone, Copy, Debug)]
+pub struct LgfxImage<'a, C = Rgb888> {
+    data: &'a [u8],
+    format: ImageFormat,
+    size: Size,
+    _color: PhantomData<C>,
+}
+
+/// Error of `LgfxImage::try_draw`.
+#[derive(Debug)]
+pub enum ImageDrawError<E> {
+    /// Failed to decode the image, or not enough memory to decode even a line of it.
+    Image(LgfxError),
+    /// Failed to draw into the target.
+    Target(E),
+}
+
+impl<'a> LgfxImage<'a> {
+    /// Creates an image from PNG or JPEG data. The format is detected from the signature.
+    pub fn new(data: &'a [u8]) -> Result<Self, LgfxError> {
+        Self::png(data).or_else(|_| Self::jpeg(data))
+    }
+    pub fn png(data: &'a [u8]) -> Result<Self, LgfxError> {
+        const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        // The first chunk must be IHDR, which starts with the width and height of the image.
+        if data.len() < 24 || data[0..8] != SIGNATURE || &data[12..16] != b"IHDR" {
+            return Err(LgfxError::InvalidImage);
+        }
+        let width = u32::from_be_bytes([data[16], data[17], data[18], data[19]]);
+        let height = u32::from_be_bytes([data[20], data[21], data[22], data[23]]);
+        Ok(Self {
+            data,
+            format: ImageFormat::Png,
+            size: Size::new(width, height),
+            _color: PhantomData,
+        })
+    }
+    pub fn jpeg(data: &'a [u8]) -> Result<Self, LgfxError> {
+        if data.len() < 4 || data[0] != 0xff || data[1] != 0xd8 {
+            return Err(LgfxError::InvalidImage);
+        }
+        // Walk through the segments until the SOFn segment, which contains the size of the frame.
+        let mut index = 2;
+        while index + 1 < data.len() {
+            if data[index] != 0xff {
+                return Err(LgfxError::InvalidImage);
+            }
+            let marker = data[index + 1];
+            index += 2;
+            match marker {
+                // Fill bytes.
+                0xff => index -= 1,
+                // Markers without segment.
+                0x01 | 0xd0..=0xd8 => {}
+                // SOF0 - SOF15 except DHT, JPG and DAC.
+                0xc0..=0xcf if marker != 0xc4 && marker != 0xc8 && marker != 0xcc => {
+                    if index + 7 > data.len() {
+                        break;
+                    }
+                    let height = u16::from_be_bytes([data[index + 3], data[index + 4]]);
+                    let width = u16::from_be_bytes([data[index + 5], data[index + 6]]);
+                    return Ok(Self {
+                        data,
+                        format: ImageFormat::Jpeg,
+                        size: Size::new(width as u32, height as u32),
+                        _color: PhantomData,
+                    });
+                }
+                // EOI or SOS before any frame header.
+                0xd9 | 0xda => break,
+                _ => {
+                    if index + 2 > data.len() {
+                        break;
+                    }
+                    // The length includes itself.
+                    let length = u16::from_be_bytes([data[index], data[index + 1]]) as usize;
+                    if length < 2 {
+                        break;
+                    }
+                    index += length;
+                }
+            }
+        }
+        Err(LgfxError::InvalidImage)
+    }
+}
+
+impl<'a, C> LgfxImage<'a, C> {
+    /// Converts the decoded pixels into the color type other than `Rgb888`.
+    /// e.g. `LgfxImage::new(data)?.with_color::<Rgb565>()`
+    pub fn with_color<D>(self) -> LgfxImage<'a, D> {
+        LgfxImage {
+            data: self.data,
+            format: self.format,
+            size: self.size,
+            _color: PhantomData,
+        }
+    }
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Decodes the part of the image starting at `offset` into the `target` at (`x`, `y`).
+    fn decode(&self, target: lgfx_target_t, x: i32, y: i32, size: Size, offset: Point) -> Result<(), LgfxError> {
+        let draw = match self.format {
+            ImageFormat::Png => lgfx_c_draw_png,
+            ImageFormat::Jpeg => lgfx_c_draw_jpg,
+        };
+        let datum: textdatum_t = textdatum_top_left;
+        let success = unsafe {
+            draw(
+                target,
+                self.data.as_ptr(),
+                self.data.len() as u32,
+                x,
+                y,
+                size.width as i32,
+                size.height as i32,
+                offset.x,
+                offset.y,
+                1.0,
+                0.0,
+                datum,
+            )
+        };
+        if success {
+            Ok(())
+        } else {
+            Err(LgfxError::DecodeImage)
+        }
+    }
+}
+
+impl<'a, C: PixelColor + From<Rgb888>> LgfxImage<'a, C> {
+    /// Draws the image with its top-left corner at `position`, and reports the decoding errors.
+    pub fn try_draw<D>(&self, target: &mut D, position: Point) -> Result<(), ImageDrawError<D::Error>>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        self.try_draw_sub_image(&mut target.translated(position), &self.bounding_box())
+    }
+
+    fn try_draw_sub_image<D>(&self, target: &mut D, area: &Rectangle) -> Result<(), ImageDrawError<D::Error>>
+    where
+        D: DrawTarget<Color = C>,
+    {
+        // Decode only the visible part of the area.
+        let visible = area
+            .intersection(&self.bounding_box())
+            .intersection(&target.bounding_box().translate(area.top_left));
+        if visible.is_zero_sized() {
+            return Ok(());
+        }
+        // Halve the height of the sprite until it can be allocated.
+        let mut band_height = visible.size.height;
+        let sprite = loop {
+            let sprite = unsafe {
+                lgfx_c_create_sprite_with_depth(core::ptr::null_mut(), visible.size.width as i32, band_height as i32, 24)
+            };
+            if !sprite.is_null() {
+                break Sprite { target: sprite };
+            }
+            if band_height == 1 {
+                return Err(ImageDrawError::Image(LgfxError::OutOfMemory));
+            }
+            band_height = band_height.div_ceil(2);
+        };
+        let mut line = vec![0u32; visible.size.width as usize];
+        let mut top = 0;
+        while top < visible.size.height {
+            let band = Rectangle::new(
+                visible.top_left + Point::new(0, top as i32),
+                Size::new(visible.size.width, band_height.min(visible.size.height - top)),
+            );
+            self.decode(sprite.target(), 0, 0, band.size, band.top_left)
+                .map_err(ImageDrawError::Image)?;
+            for y in 0..band.size.height as i32 {
+                unsafe {
+                    lgfx_c_read_rect_rgb888(sprite.target(), 0, y, band.size.width as i32, 1, line.as_mut_ptr());
+                }
+                let origin = band.top_left - area.top_left + Point::new(0, y);
+                target
+                    .fill_contiguous(
+                        &Rectangle::new(origin, Size::new(band.size.width, 1)),
+                        line.iter().map(|&raw| C::from(Rgb888::new((raw >> 16) as u8, (raw >> 8) as u8, raw as u8))),
+                    )
+                    .map_err(ImageDrawError::Target)?;
+            }
+            top += band.size.height;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, C> OriginDimensions for LgfxImage<'a, C> {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl<'a, C: PixelColor + From<Rgb888>> ImageDrawable for LgfxImage<'a, C> {
+    type Color = C;
+
+    fn draw<D>(&self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        self.draw_sub_image(target, &self.bounding_box())
+    }
+
+    fn draw_sub_image<D>(&self, target: &mut D, area: &Rectangle) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        match self.try_draw_sub_image(target, area) {
+            Err(ImageDrawError::Target(error)) => Err(error),
+            // Cannot be reported through `D::Error`. See `try_draw`.
+            Ok(()) | Err(ImageDrawError::Image(_)) => Ok(()),
+        }
+    }
+}
+
+impl<'a, Target: LgfxTarget, C> LgfxDisplay<'a, Target, C> {
+    /// Draws the image with its top-left corner at `position`.
+    /// The image is decoded directly into the display without the temporary sprite.
+    pub fn draw_image<I>(&mut self, image: &LgfxImage<I>, position: Point) -> Result<(), LgfxError> {
+        image.decode(self.target(), position.x, position.y, image.size, Point::zero())?;
+        self.mark_dirty(&Rectangle::new(position, image.size));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// PNG signature and IHDR chunk of a `width` x `height` RGB image.
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0, 0, 13];
+        data.extend_from_slice(b"IHDR");
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[8, 2, 0, 0, 0]);
+        data
+    }
+
+    /// JPEG segment with the marker and the payload.
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![0xff, marker];
+        data.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        data.extend_from_slice(payload);
+        data
+    }
+
+    /// Frame header of a `width` x `height` 8-bit grayscale frame.
+    fn sof(marker: u8, width: u16, height: u16) -> Vec<u8> {
+        let mut payload = vec![8];
+        payload.extend_from_slice(&height.to_be_bytes());
+        payload.extend_from_slice(&width.to_be_bytes());
+        payload.extend_from_slice(&[1, 1, 0x11, 0]);
+        segment(marker, &payload)
+    }
+
+    fn jpeg(segments: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0xff, 0xd8];
+        for segment in segments {
+            data.extend_from_slice(segment);
+        }
+        data.extend_from_slice(&[0xff, 0xd9]);
+        data
+    }
+
+    #[test]
+    fn reads_png_size() {
+        let data = png_header(320, 240);
+        let image = LgfxImage::png(&data).unwrap();
+        assert_eq!((image.format(), image.size()), (ImageFormat::Png, Size::new(320, 240)));
+        assert_eq!(LgfxImage::new(&data).unwrap().format(), ImageFormat::Png);
+    }
+
+    #[test]
+    fn rejects_invalid_png() {
+        let data = png_header(320, 240);
+        assert!(LgfxImage::png(&data[..23]).is_err());
+        assert!(LgfxImage::png(&[]).is_err());
+        let mut bad_signature = data.clone();
+        bad_signature[1] = b'X';
+        assert!(LgfxImage::png(&bad_signature).is_err());
+        let mut bad_chunk = data.clone();
+        bad_chunk[12..16].copy_from_slice(b"IDAT");
+        assert!(LgfxImage::png(&bad_chunk).is_err());
+    }
+
+    #[test]
+    fn reads_jpeg_size() {
+        let data = jpeg(&[segment(0xe0, b"JFIF\0\x01\x01"), sof(0xc0, 320, 240)]);
+        let image = LgfxImage::jpeg(&data).unwrap();
+        assert_eq!((image.format(), image.size()), (ImageFormat::Jpeg, Size::new(320, 240)));
+        assert_eq!(LgfxImage::new(&data).unwrap().format(), ImageFormat::Jpeg);
+        // Progressive.
+        let data = jpeg(&[sof(0xc2, 16, 8)]);
+        assert_eq!(LgfxImage::jpeg(&data).unwrap().size(), Size::new(16, 8));
+    }
+
+    #[test]
+    fn skips_non_frame_markers_in_sof_range() {
+        // DHT, JPG and DAC segments have the markers in the SOFn range.
+        let data = jpeg(&[
+            segment(0xc4, &[0; 5]),
+            segment(0xc8, &[0; 5]),
+            segment(0xcc, &[0; 5]),
+            sof(0xc1, 64, 48),
+        ]);
+        assert_eq!(LgfxImage::jpeg(&data).unwrap().size(), Size::new(64, 48));
+    }
+
+    #[test]
+    fn skips_fill_bytes_and_standalone_markers() {
+        let mut data = vec![0xff, 0xd8, 0xff, 0xff, 0xff, 0x01];
+        data.extend(sof(0xc0, 8, 8));
+        assert_eq!(LgfxImage::jpeg(&data).unwrap().size(), Size::new(8, 8));
+    }
+
+    #[test]
+    fn rejects_invalid_jpeg() {
+        assert!(LgfxImage::jpeg(&[0xff, 0xd8]).is_err());
+        assert!(LgfxImage::jpeg(&[0x00, 0xd8, 0xff, 0xc0]).is_err());
+        // Truncated frame header.
+        let data = jpeg(&[sof(0xc0, 320, 240)]);
+        assert!(LgfxImage::jpeg(&data[..8]).is_err());
+        // Truncated segment length.
+        assert!(LgfxImage::jpeg(&[0xff, 0xd8, 0xff, 0xe0, 0x00]).is_err());
+        // Segment longer than the data.
+        assert!(LgfxImage::jpeg(&[0xff, 0xd8, 0xff, 0xe0, 0x10, 0x00, 0x00]).is_err());
+        // Invalid segment length.
+        assert!(LgfxImage::jpeg(&[0xff, 0xd8, 0xff, 0xe0, 0x00, 0x01, 0xff, 0xc0]).is_err());
+        // No frame header before the scan.
+        let data = jpeg(&[segment(0xda, &[0; 4]), sof(0xc0, 320, 240)]);
+        assert!(LgfxImage::jpeg(&data).is_err());
+        // Not a marker.
+        assert!(LgfxImage::jpeg(&[0xff, 0xd8, 0x12, 0x34, 0x56]).is_err());
+    }
+}
@@ -1,5 +1,12 @@
-type Mutex<T> = std::sync::Mutex<T>;
-type MutexGuard<'a, T> = std::sync::MutexGuard<'a, T>;
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+extern crate alloc;
+
+use lock::{Acquired, Mutex, MutexGuard};
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(target_os="espidf")]
+use core::sync::atomic::{AtomicBool, Ordering};
 
 #[allow(unused)]
 #[allow(non_camel_case_types)]
@@ -38,7 +45,7 @@ pub use lgfx_sys::textdatum_bottom_centre;
 pub use lgfx_sys::textdatum_bottom_right;
 pub use lgfx_sys::font_metrics_t;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EpdMode {
     Quality = 1,
     Text = 2,
@@ -50,6 +57,23 @@ pub enum EpdMode {
 pub enum LgfxError {
     Unicode,
     Metrics,
+    InvalidImage,
+    DecodeImage,
+    UnknownEpdMode,
+    /// The display is already initialized.
+    AlreadyInitialized,
+    /// The panel is not found or failed to be initialized.
+    PanelInitFailed,
+    /// The display is locked by others.
+    WouldBlock,
+    /// The display was not unlocked within the timeout.
+    LockTimeout,
+    /// The display was locked by a thread which panicked. See `PoisonPolicy`.
+    Poisoned,
+    /// The buffer could not be allocated.
+    OutOfMemory,
+    /// A value of the configuration is out of the range supported by the driver.
+    InvalidConfig,
 }
 
 impl TryFrom<epd_mode_t> for EpdMode {
@@ -75,33 +99,96 @@ impl From<EpdMode> for epd_mode_t {
     }
 }
 
+/// Display device. Each instance owns its own LovyanGFX device.
+///
+/// The device, its bus and the simulator window are released on drop,
+/// and then the display can be set up again.
+/// `Gfx` may be dropped on any thread. On Linux, the simulator window is destroyed
+/// by the next `Gfx::handle_sdl_event` on the main thread.
+///
+/// The constructors depend on the target:
+/// * ESP-IDF: `setup` for the autodetected board, and `setup_with_config`.
+/// * Linux: `setup` for a simulator window of the size, and `setup_with_config`, which uses only the panel size.
+/// * Other targets, e.g. bare-metal `no_std`: only `setup_with_config`, with lgfx_c built and linked for the target.
 pub struct Gfx {
     target: Mutex<lgfx_target_t>,
+    poison_policy: PoisonPolicy,
+    #[cfg(target_os="espidf")]
+    autodetected: bool,
 }
+// The device is accessed only through the mutex.
 unsafe impl Send for Gfx {}
+unsafe impl Sync for Gfx {}
+impl Drop for Gfx {
+    fn drop(&mut self) {
+        unsafe { lgfx_c_free(*self.target.get_mut()) };
+        #[cfg(target_os="espidf")]
+        if self.autodetected {
+            GFX_INITIALIZED.store(false, Ordering::Release);
+        }
+    }
+}
 
 pub struct SharedLgfxTarget<'a> {
     mutex: &'a Mutex<lgfx_target_t>,
+    poison_policy: PoisonPolicy,
 }
 impl<'a> SharedLgfxTarget<'a> {
     pub fn new(mutex: &'a Mutex<lgfx_target_t>) -> Self {
-        Self { mutex }
+        Self { mutex, poison_policy: PoisonPolicy::default() }
     }
-    pub fn lock<'b>(&'b self) -> LgfxGuard<'b> {
-        LgfxGuard::<'b> {
-            update_suppressed: false,
-            guard: self.mutex.lock().unwrap(),
-        }
+    pub fn poison_policy(mut self, poison_policy: PoisonPolicy) -> Self {
+        self.poison_policy = poison_policy;
+        self
     }
-    pub fn lock_without_auto_update<'b>(&'b self) -> LgfxGuard<'b> {
-        let mut guard = self.mutex.lock().unwrap();
-        unsafe {
-            lgfx_c_start_write(guard.target());
+    fn guard<'b>(&'b self, acquired: Acquired<MutexGuard<'b, lgfx_target_t>>, update_suppressed: bool) -> Result<LgfxGuard<'b>, LgfxError> {
+        let guard = match acquired {
+            Acquired::Clean(guard) => guard,
+            Acquired::Poisoned(guard) => match self.poison_policy {
+                PoisonPolicy::Fail => return Err(LgfxError::Poisoned),
+                PoisonPolicy::Recover => {
+                    self.mutex.clear_poison();
+                    guard
+                }
+            },
+        };
+        if update_suppressed {
+            unsafe {
+                lgfx_c_start_write(*guard);
+            }
         }
-        LgfxGuard::<'b> {
-            update_suppressed: true,
+        Ok(LgfxGuard::<'b> {
+            update_suppressed,
             guard,
-        }
+        })
+    }
+    /// Locks the display.
+    /// If the auto display is enabled, the panel is refreshed after each drawing operation.
+    pub fn lock<'b>(&'b self) -> Result<LgfxGuard<'b>, LgfxError> {
+        self.guard(self.mutex.lock(), false)
+    }
+    /// Locks the display if not locked by others, or fails with `LgfxError::WouldBlock`.
+    pub fn try_lock<'b>(&'b self) -> Result<LgfxGuard<'b>, LgfxError> {
+        let acquired = self.mutex.try_lock().ok_or(LgfxError::WouldBlock)?;
+        self.guard(acquired, false)
+    }
+    /// Locks the display, or fails with `LgfxError::LockTimeout` if not unlocked by others within `timeout`.
+    #[cfg(feature = "std")]
+    pub fn lock_timeout<'b>(&'b self, timeout: Duration) -> Result<LgfxGuard<'b>, LgfxError> {
+        let acquired = self.mutex.lock_timeout(timeout).ok_or(LgfxError::LockTimeout)?;
+        self.guard(acquired, false)
+    }
+    /// Locks the display and starts a write transaction, which is ended when the guard is dropped.
+    /// If the auto display is enabled, the panel is refreshed once when the guard is dropped
+    /// instead of after each drawing operation.
+    /// Use `LgfxGuard::set_auto_display(false)` and `LgfxGuard::display` to control the refresh explicitly.
+    pub fn lock_without_auto_update<'b>(&'b self) -> Result<LgfxGuard<'b>, LgfxError> {
+        self.guard(self.mutex.lock(), true)
+    }
+    /// `lock_without_auto_update` which fails with `LgfxError::WouldBlock` if locked by others.
+    pub fn try_lock_without_auto_update<'b>(&'b self) -> Result<LgfxGuard<'b>, LgfxError> {
+        let acquired = self.mutex.try_lock().ok_or(LgfxError::WouldBlock)?;
+        self.guard(acquired, true)
     }
 }
 pub struct LgfxGuard<'a> {
@@ -113,15 +200,80 @@ impl<'a> LgfxGuard<'a> {
     pub fn is_epd(&mut self) -> bool {
         unsafe { lgfx_c_is_epd(self.target()) }
     }
-    pub fn get_epd_mode(&mut self) -> EpdMode {
+    pub fn get_epd_mode(&mut self) -> Result<EpdMode, LgfxError> {
         let epd_mode = unsafe { lgfx_c_get_epd_mode(self.target()) };
-        epd_mode.try_into().expect("unknown EPD mode returned by LGFX.")
+        epd_mode.try_into().map_err(|_| LgfxError::UnknownEpdMode)
     }
     pub fn set_epd_mode(&mut self, mode: EpdMode) {
         unsafe { lgfx_c_set_epd_mode(self.target(), mode.into()); }
     }
-    pub fn set_rotation(&mut self, rotation: u8) {
-        unsafe { lgfx_c_set_rotation(self.target(), rotation ); }
+    /// Refreshes the whole panel with the drawn contents. Only meaningful for the panels with the frame buffer, such as EPD.
+    pub fn display(&mut self) {
+        unsafe { lgfx_c_display(self.target()); }
+    }
+    /// Refreshes the region of the panel.
+    pub fn display_region(&mut self, rect: &embedded_graphics::primitives::Rectangle) {
+        unsafe {
+            lgfx_c_display_region(
+                self.target(),
+                rect.top_left.x,
+                rect.top_left.y,
+                rect.size.width as i32,
+                rect.size.height as i32,
+            );
+        }
+    }
+    /// Waits until the refresh of the panel completes.
+    pub fn wait_display(&mut self) {
+        unsafe { lgfx_c_wait_display(self.target()); }
+    }
+    /// Returns true while the panel is refreshing.
+    pub fn display_busy(&mut self) -> bool {
+        unsafe { lgfx_c_display_busy(self.target()) }
+    }
+    /// Enables or disables the refresh after each drawing operation. It is enabled by default.
+    pub fn set_auto_display(&mut self, enable: bool) {
+        unsafe { lgfx_c_set_auto_display(self.target(), enable); }
+    }
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        unsafe { lgfx_c_set_rotation(self.target(), rotation.into()); }
+    }
+    pub fn get_rotation(&mut self) -> Rotation {
+        let rotation = unsafe { lgfx_c_get_rotation(self.target()) } & 7;
+        rotation.try_into().expect("rotation must be in 0..8.")
+    }
+    /// Returns the size of the panel without the rotation.
+    pub fn physical_size(&mut self) -> embedded_graphics::prelude::Size {
+        let (width, height) = self.size();
+        self.get_rotation().physical_size(embedded_graphics::prelude::Size::new(width as u32, height as u32))
+    }
+    /// Sets the backlight brightness. 0 turns off the backlight.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        unsafe { lgfx_c_set_brightness(self.target(), brightness); }
+    }
+    pub fn get_brightness(&mut self) -> u8 {
+        unsafe { lgfx_c_get_brightness(self.target()) }
+    }
+    /// Puts the panel into the sleep mode. The contents of the panel memory are kept.
+    pub fn sleep(&mut self) {
+        unsafe { lgfx_c_sleep(self.target()); }
+    }
+    pub fn wakeup(&mut self) {
+        unsafe { lgfx_c_wakeup(self.target()); }
+    }
+    /// Enables the power save mode of the panel, which reduces the colors to lower the power consumption.
+    pub fn power_save_on(&mut self) {
+        unsafe { lgfx_c_power_save(self.target(), true); }
+    }
+    pub fn power_save_off(&mut self) {
+        unsafe { lgfx_c_power_save(self.target(), false); }
+    }
+    pub fn invert_display(&mut self, invert: bool) {
+        unsafe { lgfx_c_invert_display(self.target(), invert); }
+    }
+    /// Pushes the sprite to the locked GFX.
+    pub fn push_sprite(&mut self, sprite: &Sprite, x: i32, y: i32) {
+        unsafe { lgfx_c_push_sprite_to(sprite.target, self.target(), x, y); }
     }
 }
 
@@ -142,41 +294,79 @@ impl<'a> Drop for LgfxGuard<'a> {
 }
 
 
-static mut GFX_INITIALIZED: bool = false;
+// The autodetected board display can be set up only once, since its pins are fixed.
+#[cfg(target_os="espidf")]
+static GFX_INITIALIZED: AtomicBool = AtomicBool::new(false);
 impl Gfx {
-    #[cfg(target_os="espidf")]
-    pub fn setup() -> Option<Gfx> {
-        if unsafe { GFX_INITIALIZED } {
-            None
+    fn from_target(target: lgfx_target_t) -> Result<Gfx, LgfxError> {
+        if target.is_null() {
+            Err(LgfxError::PanelInitFailed)
         } else {
-            unsafe {
-                GFX_INITIALIZED = true;
-            }
-            Some(Gfx {
-                target: Mutex::new(unsafe { lgfx_c_setup() }),
+            Ok(Gfx {
+                target: Mutex::new(target),
+                poison_policy: PoisonPolicy::default(),
+                #[cfg(target_os="espidf")]
+                autodetected: false,
             })
         }
     }
-    #[cfg(target_os="linux")]
-    pub fn setup(width: i32, height: i32) -> Option<Gfx> {
-        if unsafe { GFX_INITIALIZED } {
-            None
-        } else {
-            unsafe {
-                GFX_INITIALIZED = true;
+    /// Initializes the display of the board detected automatically.
+    /// Fails with `AlreadyInitialized` if already initialized and not dropped yet,
+    /// or `PanelInitFailed` if no panel is detected.
+    /// Use `setup_with_config` for the additional displays.
+    #[cfg(target_os="espidf")]
+    pub fn setup() -> Result<Gfx, LgfxError> {
+        if GFX_INITIALIZED.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return Err(LgfxError::AlreadyInitialized);
+        }
+        match Self::from_target(unsafe { lgfx_c_setup() }) {
+            Ok(mut gfx) => {
+                gfx.autodetected = true;
+                Ok(gfx)
+            }
+            Err(err) => {
+                GFX_INITIALIZED.store(false, Ordering::Release);
+                Err(err)
             }
-            Some(Gfx {
-                target: Mutex::new(unsafe { lgfx_c_setup_with_size(width, height) }),
-            })
         }
     }
+    /// Opens a simulator window. Each call opens a new window.
+    #[cfg(target_os="linux")]
+    pub fn setup(width: i32, height: i32) -> Result<Gfx, LgfxError> {
+        Self::from_target(unsafe { lgfx_c_setup_with_size(width, height) })
+    }
+    /// Initializes the display with the explicit hardware configuration instead of the board autodetection.
+    /// Multiple displays can be set up with the different configurations.
+    /// Fails with `PanelInitFailed` if the panel is not initialized or the configuration is not supported.
+    /// On the simulator, only the panel size is used.
+    pub fn setup_with_config(config: &config::DisplayConfig) -> Result<Gfx, LgfxError> {
+        let (bus, panel, light, touch) = config.raw();
+        Self::from_target(unsafe {
+            lgfx_c_setup_with_config(
+                &bus,
+                &panel,
+                light.as_ref().map_or(core::ptr::null(), |light| light as *const _),
+                touch.as_ref().map_or(core::ptr::null(), |touch| touch as *const _),
+            )
+        })
+    }
     pub fn as_shared<'a>(&'a self) -> SharedLgfxTarget<'a> {
-        SharedLgfxTarget::new(&self.target)
+        SharedLgfxTarget::new(&self.target).poison_policy(self.poison_policy)
+    }
+    /// Sets how to handle the lock left by a thread which panicked while drawing.
+    pub fn set_poison_policy(&mut self, poison_policy: PoisonPolicy) {
+        self.poison_policy = poison_policy;
+    }
+    /// Clears the poison of the lock to use the display again with `PoisonPolicy::Fail`.
+    pub fn clear_poison(&self) {
+        self.target.clear_poison();
     }
     pub fn create_sprite(&self, w: i32, h: i32) -> Result<Sprite, ()> {
         Sprite::new(self, w, h)
     }
 
+    /// Pumps the SDL events and destroys the windows of the dropped displays.
+    /// Must be called on the main thread.
     #[cfg(target_os="linux")]
     pub fn handle_sdl_event() {
         unsafe { lgfx_c_panel_sdl_event_handler(); }
@@ -197,12 +387,20 @@ where
     }
 }
 
+/// Off-screen drawing buffer.
+///
+/// Drawing to the sprite requires `&mut`, so a sprite can be rendered in another thread (`Send`)
+/// but cannot be shared among threads (not `Sync`).
+/// Pushing to the display locks the display, so it never races with the drawing through `LgfxGuard`.
 pub struct Sprite {
     target: lgfx_target_t,
 }
+// The sprite owns its buffer, and the LovyanGFX object is accessed only through this handle.
+unsafe impl Send for Sprite {}
 impl Sprite {
     fn new(gfx: &Gfx, w: i32, h: i32) -> Result<Self, ()> {
-        let mut target = gfx.as_shared().mutex.lock().unwrap();
+        let shared = gfx.as_shared();
+        let target = shared.lock().map_err(|_| ())?;
         let sprite = unsafe { lgfx_c_create_sprite(target.target(), w, h) };
         if sprite == core::ptr::null_mut() {
             Err(())
@@ -212,10 +410,13 @@ impl Sprite {
     }
 
     /// Pushes the sprite to the GFX.
-    /// gfx: The parent GFX of this sprite.
-    pub fn push_sprite(&self, gfx: &Gfx, x: i32, y: i32) {
-        let _target = gfx.as_shared().mutex.lock().unwrap(); // Just lock the parent GFX.
-        unsafe { lgfx_c_push_sprite(self.target, x, y) };
+    /// gfx: The GFX to push to, which may be other than the one the sprite was created from.
+    /// Blocks while the GFX is locked by other threads. Use `LgfxGuard::push_sprite` while holding the lock.
+    pub fn push_sprite(&self, gfx: &Gfx, x: i32, y: i32) -> Result<(), LgfxError> {
+        let shared = gfx.as_shared();
+        let mut target = shared.lock()?;
+        target.push_sprite(self, x, y);
+        Ok(())
     }
 }
 impl LgfxTarget for Sprite {
@@ -261,6 +462,24 @@ impl Color for ColorRgb332 {
     }
 }
 
+#[derive(Debug, Copy, Clone)]
+pub struct ColorRgb565 {
+    raw: u16,
+}
+impl ColorRgb565 {
+    pub fn new(raw: u16) -> Self {
+        Self { raw }
+    }
+}
+impl Color for ColorRgb565 {
+    fn as_u32(&self) -> u32 {
+        let r = ((self.raw >> 11) & 0x1f) as u32;
+        let g = ((self.raw >> 5) & 0x3f) as u32;
+        let b = (self.raw & 0x1f) as u32;
+        (((r << 3) | (r >> 2)) << 16) | (((g << 2) | (g >> 4)) << 8) | ((b << 3) | (b >> 2))
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct ColorRgb888 {
     raw: u32,
@@ -314,6 +533,26 @@ where
         }
     }
 }
+impl<Target> DrawPrimitives<ColorRgb565> for Target
+where
+    Target: LgfxTarget,
+{
+    fn clear(&mut self, color: ColorRgb565) {
+        unsafe {
+            lgfx_c_clear_rgb565(self.target(), color.raw);
+        }
+    }
+    fn fill_rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: ColorRgb565) {
+        unsafe {
+            lgfx_c_fill_rect_rgb565(self.target(), x, y, w, h, color.raw);
+        }
+    }
+    fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, color: ColorRgb565) {
+        unsafe {
+            lgfx_c_draw_line_rgb565(self.target(), x0, y0, x1, y1, color.raw);
+        }
+    }
+}
 impl<Target> DrawPrimitives<ColorRgb888> for Target
 where
     Target: LgfxTarget,
@@ -335,70 +574,141 @@ where
     }
 }
 
+/// Text drawing traits take the background color as `Option<C>`.
+/// `None` draws only the glyph pixels and leaves the background untouched (transparent).
+/// `Some(bg)` fills the background of the glyphs, even if `bg` is same as `fg`.
 pub trait DrawChar<C: Color> {
-    fn draw_char(&self, c: char, x: i32, y: i32, fg: C, bg: C, size_x: f32, size_y: f32) -> i32;
+    fn draw_char(&mut self, c: char, x: i32, y: i32, fg: C, bg: Option<C>, size_x: f32, size_y: f32) -> i32;
 }
 pub trait DrawChars<C: Color> {
-    fn draw_chars(&self, s: &str, x: i32, y: i32, fg: C, bg: C, size_x: f32, size_y: f32) -> i32;
+    fn draw_chars(&mut self, s: &str, x: i32, y: i32, fg: C, bg: Option<C>, size_x: f32, size_y: f32) -> i32;
 }
 
 pub trait DrawString<C: Color> {
+    /// Measures the extent of the string. The result does not depend on the background mode,
+    /// so it can be used to lay out both opaque and transparent text.
     fn measure_string(&self, s: &str, size_x: f32, size_y: f32) -> (i32, i32);
-    fn draw_string(&self, s: &str, x: i32, y: i32, fg: C, bg: C, size_x: f32, size_y: f32, datum: textdatum_t) -> (i32, i32);
+    fn draw_string(&mut self, s: &str, x: i32, y: i32, fg: C, bg: Option<C>, size_x: f32, size_y: f32, datum: textdatum_t) -> (i32, i32);
 }
 
 
+/// Background of a glyph drawn by `drawChar` of LovyanGFX,
+/// which draws no background if the foreground and background colors are same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum GlyphBackground<R> {
+    /// Leaves the background pixels untouched.
+    Transparent,
+    /// Fills the background pixels with the color.
+    Opaque(R),
+    /// Same color as the foreground, which `drawChar` would regard as transparent.
+    /// The cell is filled before the glyph is drawn.
+    Filled(R),
+}
+
+impl<R: Copy + PartialEq> GlyphBackground<R> {
+    fn new(fg: R, bg: Option<R>) -> Self {
+        match bg {
+            None => Self::Transparent,
+            Some(bg) if bg == fg => Self::Filled(bg),
+            Some(bg) => Self::Opaque(bg),
+        }
+    }
+    /// Background color to pass to `drawChar`.
+    fn draw_char_bg(&self, fg: R) -> R {
+        match *self {
+            Self::Opaque(bg) => bg,
+            Self::Transparent | Self::Filled(_) => fg,
+        }
+    }
+}
+
+/// Draws the UTF-16 code units of `c` with `draw`, which calls `drawChar` of the color type.
+/// `fill` fills the cell of the glyph returned by `cell` if needed.
+fn draw_glyph<R: Copy + PartialEq>(
+    c: char,
+    fg: R,
+    bg: Option<R>,
+    cell: impl FnOnce() -> Option<embedded_graphics::primitives::Rectangle>,
+    mut fill: impl FnMut(&embedded_graphics::primitives::Rectangle, R),
+    mut draw: impl FnMut(u16, R, R) -> i32,
+) -> i32 {
+    let background = GlyphBackground::new(fg, bg);
+    if let GlyphBackground::Filled(bg) = background {
+        if let Some(cell) = cell() {
+            fill(&cell, bg);
+        }
+    }
+    let bg = background.draw_char_bg(fg);
+    let mut buf = [0u16; 2];
+    c.encode_utf16(&mut buf).iter().map(|&unicode| draw(unicode, fg, bg)).sum()
+}
+
+/// Area of the glyph drawn at (x, y) with the current font of the target.
+fn glyph_cell<Target: FontManupulation>(
+    target: &Target,
+    c: char,
+    x: i32,
+    y: i32,
+    size_x: f32,
+    size_y: f32,
+) -> Option<embedded_graphics::primitives::Rectangle> {
+    let font = target.get_font().ok()?;
+    let mut buf = [0u8; 4];
+    Some(font.chars_area(c.encode_utf8(&mut buf), x, y, size_x, size_y))
+}
+
 impl<Target> DrawChar<ColorRgb332> for Target
 where
     Target: LgfxTarget,
 {
     fn draw_char(
-        &self,
+        &mut self,
         c: char,
         x: i32,
         y: i32,
         fg: ColorRgb332,
-        bg: ColorRgb332,
+        bg: Option<ColorRgb332>,
         size_x: f32,
         size_y: f32,
     ) -> i32 {
-        let mut buf = [0u16; 2];
-        let encoded = c.encode_utf16(&mut buf);
-        let mut width = 0;
-
-        width += if encoded.len() >= 1 {
-            unsafe {
-                lgfx_c_draw_char_rgb332(
-                    self.target(),
-                    x,
-                    y,
-                    encoded[0],
-                    fg.raw,
-                    bg.raw,
-                    size_x,
-                    size_y,
-                ) as i32
-            }
-        } else {
-            0
-        };
-        width += if encoded.len() >= 2 {
-            unsafe {
-                lgfx_c_draw_char_rgb332(
-                    self.target(),
-                    x,
-                    y,
-                    encoded[1],
-                    fg.raw,
-                    bg.raw,
-                    size_x,
-                    size_y,
-                ) as i32
-            }
-        } else {
-            0
-        };
-        width
+        let target = self.target();
+        draw_glyph(
+            c,
+            fg.raw,
+            bg.map(|bg| bg.raw),
+            || glyph_cell(self, c, x, y, size_x, size_y),
+            |cell, color| unsafe {
+                lgfx_c_fill_rect_rgb332(target, cell.top_left.x, cell.top_left.y, cell.size.width as i32, cell.size.height as i32, color)
+            },
+            |unicode, fg, bg| unsafe { lgfx_c_draw_char_rgb332(target, x, y, unicode, fg, bg, size_x, size_y) as i32 },
+        )
+    }
+}
+impl<Target> DrawChar<ColorRgb565> for Target
+where
+    Target: LgfxTarget,
+{
+    fn draw_char(
+        &mut self,
+        c: char,
+        x: i32,
+        y: i32,
+        fg: ColorRgb565,
+        bg: Option<ColorRgb565>,
+        size_x: f32,
+        size_y: f32,
+    ) -> i32 {
+        let target = self.target();
+        draw_glyph(
+            c,
+            fg.raw,
+            bg.map(|bg| bg.raw),
+            || glyph_cell(self, c, x, y, size_x, size_y),
+            |cell, color| unsafe {
+                lgfx_c_fill_rect_rgb565(target, cell.top_left.x, cell.top_left.y, cell.size.width as i32, cell.size.height as i32, color)
+            },
+            |unicode, fg, bg| unsafe { lgfx_c_draw_char_rgb565(target, x, y, unicode, fg, bg, size_x, size_y) as i32 },
+        )
     }
 }
 impl<Target> DrawChar<ColorRgb888> for Target
@@ -406,52 +716,26 @@ where
     Target: LgfxTarget,
 {
     fn draw_char(
-        &self,
+        &mut self,
         c: char,
         x: i32,
         y: i32,
         fg: ColorRgb888,
-        bg: ColorRgb888,
+        bg: Option<ColorRgb888>,
         size_x: f32,
         size_y: f32,
     ) -> i32 {
-        let mut buf = [0u16; 2];
-        let encoded = c.encode_utf16(&mut buf);
-        let mut width = 0;
-
-        width += if encoded.len() >= 1 {
-            unsafe {
-                lgfx_c_draw_char_rgb888(
-                    self.target(),
-                    x,
-                    y,
-                    encoded[0],
-                    fg.raw,
-                    bg.raw,
-                    size_x,
-                    size_y,
-                ) as i32
-            }
-        } else {
-            0
-        };
-        width += if encoded.len() >= 2 {
-            unsafe {
-                lgfx_c_draw_char_rgb888(
-                    self.target(),
-                    x,
-                    y,
-                    encoded[1],
-                    fg.raw,
-                    bg.raw,
-                    size_x,
-                    size_y,
-                ) as i32
-            }
-        } else {
-            0
-        };
-        width
+        let target = self.target();
+        draw_glyph(
+            c,
+            fg.raw,
+            bg.map(|bg| bg.raw),
+            || glyph_cell(self, c, x, y, size_x, size_y),
+            |cell, color| unsafe {
+                lgfx_c_fill_rect_rgb888(target, cell.top_left.x, cell.top_left.y, cell.size.width as i32, cell.size.height as i32, color)
+            },
+            |unicode, fg, bg| unsafe { lgfx_c_draw_char_rgb888(target, x, y, unicode, fg, bg, size_x, size_y) as i32 },
+        )
     }
 }
 impl<Target, C> DrawChars<C> for Target
@@ -459,7 +743,7 @@ where
     Target: LgfxTarget + DrawChar<C>,
     C: Color,
 {
-    fn draw_chars(&self, s: &str, x: i32, y: i32, fg: C, bg: C, size_x: f32, size_y: f32) -> i32 {
+    fn draw_chars(&mut self, s: &str, x: i32, y: i32, fg: C, bg: Option<C>, size_x: f32, size_y: f32) -> i32 {
         let mut width = 0;
         for c in s.chars() {
             width += self.draw_char(c, x + width, y, fg.clone(), bg.clone(), size_x, size_y);
@@ -474,14 +758,55 @@ where
     C: Color,
 {
     fn measure_string(&self, s: &str, size_x: f32, size_y: f32) -> (i32, i32) {
-        let scaling_x = (size_x * 65536.0).floor() as i32;
-        let scaling_y = (size_y * 65536.0).floor() as i32;
+        self.get_font().unwrap().measure_string(s, size_x, size_y)
+    }
+    fn draw_string(&mut self, s: &str, x: i32, y: i32, fg: C, bg: Option<C>, size_x: f32, size_y: f32, datum: textdatum_t) -> (i32, i32) {
+        let font = self.get_font().unwrap();
+        let (string_width, string_height) = font.measure_string(s, size_x, size_y);
+        let (x, y) = font.string_origin(s, x, y, size_x, size_y, datum);
+
+        let mut width = 0;
+        for c in s.chars() {
+            width += self.draw_char(c, x + width, y, fg.clone(), bg.clone(), size_x, size_y);
+        }
+        (string_width, string_height)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LgfxFont {
+    pub(crate) ptr: *const core::ffi::c_void,
+}
+unsafe impl Sync for LgfxFont {}
+unsafe impl Send for LgfxFont {}
+
+/// Horizontal and vertical extent of a string rendered with a font.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct StringExtent {
+    /// Space reserved before the first glyph if it has negative x offset.
+    pub lead: i32,
+    /// Width of the bounding box including `lead`.
+    pub width: i32,
+    /// Horizontal advance to the next string position including `lead`.
+    pub advance: i32,
+    /// Maximum height of the glyphs.
+    pub height: i32,
+}
+
+impl LgfxFont {
+    /// Measures the width and height of the string scaled by `size_x` and `size_y`.
+    pub fn measure_string(&self, s: &str, size_x: f32, size_y: f32) -> (i32, i32) {
+        let extent = self.string_extent(s, size_x, size_y);
+        (extent.width, extent.height)
+    }
+    pub(crate) fn string_extent(&self, s: &str, size_x: f32, size_y: f32) -> StringExtent {
+        let scaling_x = math::fixed16(size_x);
+        let scaling_y = math::fixed16(size_y);
+        let mut extent = StringExtent::default();
         let mut left = 0;
         let mut right = 0;
-        let mut max_height = 0;
-        let font = self.get_font().unwrap();
         for c in s.chars() {
-            if let Ok(metrics) = font.metrics(c) {
+            if let Ok(metrics) = self.metrics(c) {
                 let scaled_offset = ((metrics.x_offset as i32) * scaling_x) >> 16;
                 let scaled_advance = ((metrics.x_advance as i32) * scaling_x) >> 16;
                 let scaled_width = ((metrics.width as i32) * scaling_x) >> 16;
@@ -489,19 +814,22 @@ where
                 if left == 0 && right == 0 && scaled_offset < 0 {
                     left = -scaled_offset;
                     right = -scaled_offset;
+                    extent.lead = -scaled_offset;
                 }
                 right = left + scaled_advance.max(scaled_width + scaled_offset);
                 left += scaled_advance;
-                max_height = max_height.max(scaled_height);
+                extent.height = extent.height.max(scaled_height);
             }
         }
-        (right, max_height)
+        extent.width = right;
+        extent.advance = left;
+        extent
     }
-    fn draw_string(&self, s: &str, mut x: i32, mut y: i32, fg: C, bg: C, size_x: f32, size_y: f32, datum: textdatum_t) -> (i32, i32) {
+    /// Returns the position to draw the characters of the string aligned by `datum` at (x, y).
+    pub(crate) fn string_origin(&self, s: &str, mut x: i32, mut y: i32, size_x: f32, size_y: f32, datum: textdatum_t) -> (i32, i32) {
         let (string_width, string_height) = self.measure_string(s, size_x, size_y);
-        let metrics = self.get_font().unwrap().default_metrics();
-        //let scaling_x = (size_x * 65536.0).floor() as i32;
-        let scaling_y = (size_y * 65536.0).floor() as i32;
+        let metrics = self.default_metrics();
+        let scaling_y = math::fixed16(size_y);
 
         if (datum & textdatum_middle_left) != 0 {
             y -= string_height >> 1;
@@ -511,29 +839,26 @@ where
             y -= (metrics.baseline as i32 * scaling_y) >> 16;
         }
         y -= (metrics.y_offset as i32 * scaling_y) >> 16;
-        
+
         if (datum & textdatum_top_center) != 0 {
             x -= string_width >> 1;
         } else if (datum & textdatum_top_right) != 0  {
             x -= string_width;
         }
-        
-        let mut width = 0;
-        for c in s.chars() {
-            width += self.draw_char(c, x + width, y, fg.clone(), bg.clone(), size_x, size_y);
-        }
-        (string_width, string_height)
+        (x, y)
+    }
+    /// Returns the area covered by the characters of the string drawn at (x, y), including their background.
+    pub(crate) fn chars_area(&self, s: &str, x: i32, y: i32, size_x: f32, size_y: f32) -> embedded_graphics::primitives::Rectangle {
+        let extent = self.string_extent(s, size_x, size_y);
+        let metrics = self.default_metrics();
+        let scaling_y = math::fixed16(size_y);
+        let top = y + ((metrics.y_offset as i32 * scaling_y) >> 16);
+        let height = ((metrics.height as i32 * scaling_y) >> 16).max(extent.height);
+        embedded_graphics::primitives::Rectangle::new(
+            embedded_graphics::prelude::Point::new(x - extent.lead, top),
+            embedded_graphics::prelude::Size::new(extent.width.max(extent.advance).max(0) as u32, height.max(0) as u32),
+        )
     }
-}
-
-#[derive(Clone, Copy, Debug)]
-pub struct LgfxFont {
-    pub(crate) ptr: *const core::ffi::c_void,
-}
-unsafe impl Sync for LgfxFont {}
-unsafe impl Send for LgfxFont {}
-
-impl LgfxFont {
     pub fn default_metrics(&self) -> font_metrics_t  {
         let mut metrics = font_metrics_t::default();
         unsafe {
@@ -673,45 +998,111 @@ impl<'a> DrawPng<'a> {
     }
 }
 
-pub struct LgfxDisplay<'a, Target: LgfxTarget> {
+/// embedded-graphics color types which can be drawn with the native color path of LovyanGFX.
+pub trait LgfxPixelColor: embedded_graphics::prelude::PixelColor {
+    type Native: Color;
+    fn to_native(self) -> Self::Native;
+}
+impl LgfxPixelColor for embedded_graphics::pixelcolor::Rgb565 {
+    type Native = ColorRgb565;
+    fn to_native(self) -> Self::Native {
+        ColorRgb565::new(embedded_graphics::pixelcolor::IntoStorage::into_storage(self))
+    }
+}
+impl LgfxPixelColor for embedded_graphics::pixelcolor::Rgb888 {
+    type Native = ColorRgb888;
+    fn to_native(self) -> Self::Native {
+        ColorRgb888::new(embedded_graphics::pixelcolor::IntoStorage::into_storage(self))
+    }
+}
+impl LgfxPixelColor for embedded_graphics::pixelcolor::Gray8 {
+    type Native = ColorRgb888;
+    fn to_native(self) -> Self::Native {
+        let luma = embedded_graphics::pixelcolor::GrayColor::luma(&self) as u32;
+        ColorRgb888::new((luma << 16) | (luma << 8) | luma)
+    }
+}
+impl LgfxPixelColor for embedded_graphics::pixelcolor::BinaryColor {
+    type Native = ColorRgb332;
+    fn to_native(self) -> Self::Native {
+        // On is white, which is the foreground color of e-paper and 1bpp sprites.
+        match self {
+            embedded_graphics::pixelcolor::BinaryColor::On => ColorRgb332::new(0xff),
+            embedded_graphics::pixelcolor::BinaryColor::Off => ColorRgb332::new(0x00),
+        }
+    }
+}
+
+/// embedded-graphics adapter of a LovyanGFX target.
+/// The color type `C` can be `Rgb565`, `Rgb888`, `Gray8` or `BinaryColor`.
+pub struct LgfxDisplay<'a, Target: LgfxTarget, C = embedded_graphics::pixelcolor::Rgb888> {
     target: &'a mut Target,
+    dirty: Option<&'a mut dirty::DirtyRegion>,
+    _color: core::marker::PhantomData<C>,
 }
 impl<'a, Target: LgfxTarget> LgfxDisplay<'a, Target> {
     pub fn new(target: &'a mut Target) -> Self {
-        Self { target }
+        Self::with_color(target)
+    }
+}
+impl<'a, Target: LgfxTarget, C> LgfxDisplay<'a, Target, C> {
+    /// Creates an adapter with the color type other than `Rgb888`.
+    /// e.g. `LgfxDisplay::<_, Rgb565>::with_color(&mut target)`
+    pub fn with_color(target: &'a mut Target) -> Self {
+        Self { target, dirty: None, _color: core::marker::PhantomData }
+    }
+    /// Records the area of the drawing into `dirty`.
+    pub fn tracked(mut self, dirty: &'a mut dirty::DirtyRegion) -> Self {
+        self.dirty = Some(dirty);
+        self
+    }
+    pub(crate) fn mark_dirty(&mut self, rect: &embedded_graphics::primitives::Rectangle) {
+        if let Some(dirty) = self.dirty.as_deref_mut() {
+            dirty.add(rect);
+        }
     }
 }
-impl<'a, Target: LgfxTarget> LgfxTarget for LgfxDisplay<'a, Target> {
+impl<'a, Target: LgfxTarget, C> LgfxTarget for LgfxDisplay<'a, Target, C> {
     fn target(&self) -> lgfx_target_t {
         self.target.target()
     }
 }
-impl<'a, Target: LgfxTarget> embedded_graphics::prelude::OriginDimensions
-    for LgfxDisplay<'a, Target>
+impl<'a, Target: LgfxTarget, C> embedded_graphics::prelude::OriginDimensions
+    for LgfxDisplay<'a, Target, C>
 {
     fn size(&self) -> embedded_graphics::prelude::Size {
         let size = Screen::size(self);
         embedded_graphics::prelude::Size::new(size.0 as u32, size.1 as u32)
     }
 }
-impl<'a, Target: LgfxTarget> embedded_graphics::prelude::DrawTarget for LgfxDisplay<'a, Target> {
-    type Color = embedded_graphics::pixelcolor::Rgb888;
+impl<'a, Target: LgfxTarget, C> embedded_graphics::prelude::DrawTarget for LgfxDisplay<'a, Target, C>
+where
+    C: LgfxPixelColor,
+    Self: DrawPrimitives<C::Native>,
+{
+    type Color = C;
     type Error = core::convert::Infallible;
 
     fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
     where
         I: IntoIterator<Item = embedded_graphics::Pixel<Self::Color>>,
     {
+        let mut drawn: Option<embedded_graphics::primitives::Rectangle> = None;
         for embedded_graphics::Pixel(coord, color) in pixels.into_iter() {
             self.draw_line(
                 coord.x,
                 coord.y,
                 coord.x,
                 coord.y,
-                ColorRgb888::new(embedded_graphics::pixelcolor::IntoStorage::into_storage(
-                    color,
-                )),
+                color.to_native(),
             );
+            if self.dirty.is_some() {
+                let pixel = embedded_graphics::primitives::Rectangle::new(coord, embedded_graphics::prelude::Size::new(1, 1));
+                drawn = Some(drawn.map_or(pixel, |drawn| dirty::envelope(&drawn, &pixel)));
+            }
+        }
+        if let Some(drawn) = drawn {
+            self.mark_dirty(&drawn);
         }
         Ok(())
     }
@@ -725,18 +1116,146 @@ impl<'a, Target: LgfxTarget> embedded_graphics::prelude::DrawTarget for LgfxDisp
             area.top_left.y as i32,
             area.size.width as i32,
             area.size.height as i32,
-            ColorRgb888::new(embedded_graphics::pixelcolor::IntoStorage::into_storage(
-                color,
-            )),
+            color.to_native(),
         );
+        self.mark_dirty(area);
         Ok(())
     }
 }
 
 // TODO: ピクセルバッファを確保してpush imageするfill_contiguous実装を作る
 
+pub mod asynch;
+#[cfg(feature = "std")]
+pub mod button;
+pub mod config;
+pub mod dirty;
+pub mod epd;
+#[cfg(feature = "std")]
+pub mod event;
+pub mod frame;
+pub mod gesture;
+mod image;
+mod lock;
+mod math;
+#[cfg(feature = "std")]
+pub mod power;
+pub mod present;
+mod rotation;
+mod text;
+mod touch;
+pub use image::{ImageDrawError, ImageFormat, LgfxImage};
+pub use lock::PoisonPolicy;
+pub use rotation::Rotation;
+pub use text::LgfxCharacterStyle;
+pub use touch::{TouchCalibration, TouchPoint, MAX_TOUCH_POINTS};
+
 // Font definitions
 pub mod fonts {
     use super::LgfxFont;
     include!(concat!(env!("OUT_DIR"), "/lgfx_fonts.rs"));
 }
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::pixelcolor::{BinaryColor, Gray8, Rgb565, Rgb888};
+    use embedded_graphics::prelude::RgbColor;
+
+    use super::*;
+
+    #[test]
+    fn passes_rgb565_through() {
+        assert_eq!(Rgb565::new(31, 0, 0).to_native().raw, 0xf800);
+        assert_eq!(Rgb565::new(0, 63, 0).to_native().raw, 0x07e0);
+        assert_eq!(Rgb565::new(1, 2, 3).to_native().raw, (1 << 11) | (2 << 5) | 3);
+    }
+
+    #[test]
+    fn passes_rgb888_through() {
+        assert_eq!(Rgb888::new(0x12, 0x34, 0x56).to_native().as_u32(), 0x123456);
+    }
+
+    #[test]
+    fn expands_gray8_to_rgb888() {
+        assert_eq!(Gray8::new(0x00).to_native().as_u32(), 0x000000);
+        assert_eq!(Gray8::new(0x5a).to_native().as_u32(), 0x5a5a5a);
+        assert_eq!(Gray8::new(0xff).to_native().as_u32(), 0xffffff);
+    }
+
+    #[test]
+    fn maps_binary_color_to_white_and_black() {
+        assert_eq!(BinaryColor::On.to_native().raw, 0xff);
+        assert_eq!(BinaryColor::Off.to_native().raw, 0x00);
+        assert_eq!(BinaryColor::On.to_native().as_u32(), 0xffffff);
+        assert_eq!(BinaryColor::Off.to_native().as_u32(), 0x000000);
+    }
+
+    #[test]
+    fn expands_rgb565_to_rgb888() {
+        assert_eq!(ColorRgb565::new(0x0000).as_u32(), 0x000000);
+        assert_eq!(ColorRgb565::new(0xffff).as_u32(), 0xffffff);
+        assert_eq!(ColorRgb565::new(0xf800).as_u32(), 0xff0000);
+        assert_eq!(ColorRgb565::new(0x07e0).as_u32(), 0x00ff00);
+        assert_eq!(ColorRgb565::new(0x001f).as_u32(), 0x0000ff);
+        // The high bits are replicated into the low bits.
+        assert_eq!(ColorRgb565::new(0x8410).as_u32(), 0x848284);
+        // Round trip through the embedded-graphics conversion.
+        assert_eq!(ColorRgb565::new(Rgb565::RED.to_native().raw).as_u32(), 0xff0000);
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum GlyphOp {
+        Fill { y: i32, color: u8 },
+        Draw { unicode: u16, fg: u8, bg: u8 },
+    }
+
+    /// Records the operations of `draw_glyph`. The cell is placed at y = code point of `c` to identify it.
+    fn glyph_ops(c: char, fg: u8, bg: Option<u8>) -> Vec<GlyphOp> {
+        let ops = core::cell::RefCell::new(Vec::new());
+        let cell = embedded_graphics::primitives::Rectangle::new(
+            embedded_graphics::prelude::Point::new(0, c as i32),
+            embedded_graphics::prelude::Size::new(6, 8),
+        );
+        let width = draw_glyph(
+            c,
+            fg,
+            bg,
+            || Some(cell),
+            |cell, color| ops.borrow_mut().push(GlyphOp::Fill { y: cell.top_left.y, color }),
+            |unicode, fg, bg| {
+                ops.borrow_mut().push(GlyphOp::Draw { unicode, fg, bg });
+                6
+            },
+        );
+        assert_eq!(width as usize, 6 * c.len_utf16());
+        ops.into_inner()
+    }
+
+    #[test]
+    fn draws_glyph_without_background() {
+        // drawChar of LovyanGFX leaves the background untouched if fg == bg.
+        assert_eq!(glyph_ops('A', 0xe0, None), [GlyphOp::Draw { unicode: 0x41, fg: 0xe0, bg: 0xe0 }]);
+    }
+
+    #[test]
+    fn draws_glyph_with_background() {
+        assert_eq!(glyph_ops('A', 0xe0, Some(0x03)), [GlyphOp::Draw { unicode: 0x41, fg: 0xe0, bg: 0x03 }]);
+        assert_eq!(GlyphBackground::new(0xe0u8, Some(0x03)), GlyphBackground::Opaque(0x03));
+    }
+
+    #[test]
+    fn fills_background_of_foreground_color() {
+        assert_eq!(
+            glyph_ops('A', 0xe0, Some(0xe0)),
+            [GlyphOp::Fill { y: 0x41, color: 0xe0 }, GlyphOp::Draw { unicode: 0x41, fg: 0xe0, bg: 0xe0 }]
+        );
+    }
+
+    #[test]
+    fn draws_surrogate_pairs() {
+        assert_eq!(
+            glyph_ops('\u{1f600}', 0xff, None),
+            [GlyphOp::Draw { unicode: 0xd83d, fg: 0xff, bg: 0xff }, GlyphOp::Draw { unicode: 0xde00, fg: 0xff, bg: 0xff }]
+        );
+    }
+}
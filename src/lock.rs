@@ -0,0 +1,293 @@
+//! Lock backends of the display.
+//!
+//! `std::sync::Mutex` is used by default.
+//! With the `critical-section` feature, a lock whose state is guarded by `critical_section` is used instead,
+//! which works on the targets without the atomic compare-and-swap and the OS mutex.
+//! The `critical-section` feature is required without the `std` feature.
+
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+#[cfg(not(any(feature = "std", feature = "critical-section")))]
+compile_error!("The `critical-section` feature is required without the `std` feature.");
+
+/// Interval to retry acquiring the lock in `lock_timeout`.
+#[cfg(feature = "std")]
+const RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// How to handle the lock released by a thread which panicked while drawing.
+/// Without the `std` feature, the lock is never poisoned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// Fails with `LgfxError::Poisoned` until `Gfx::clear_poison` is called.
+    #[default]
+    Fail,
+    /// Clears the poison and continues to use the display.
+    Recover,
+}
+
+/// Result of acquiring the lock.
+pub(crate) enum Acquired<G> {
+    Clean(G),
+    /// Acquired the lock released by a panicked thread.
+    Poisoned(G),
+}
+
+#[cfg(not(feature = "critical-section"))]
+pub use self::std_mutex::{Mutex, MutexGuard};
+#[cfg(feature = "critical-section")]
+pub use self::cs_mutex::{Mutex, MutexGuard};
+
+#[cfg(feature = "std")]
+impl<T> Mutex<T> {
+    /// Tries to acquire the lock until `timeout` elapses.
+    pub(crate) fn lock_timeout(&self, timeout: Duration) -> Option<Acquired<MutexGuard<'_, T>>> {
+        let start = Instant::now();
+        loop {
+            if let Some(acquired) = self.try_lock() {
+                return Some(acquired);
+            }
+            let elapsed = start.elapsed();
+            if elapsed >= timeout {
+                return None;
+            }
+            std::thread::sleep(RETRY_INTERVAL.min(timeout - elapsed));
+        }
+    }
+}
+
+#[cfg(not(feature = "critical-section"))]
+mod std_mutex {
+    use std::sync::TryLockError;
+
+    use super::Acquired;
+
+    pub struct Mutex<T>(std::sync::Mutex<T>);
+    pub type MutexGuard<'a, T> = std::sync::MutexGuard<'a, T>;
+
+    impl<T> Mutex<T> {
+        pub fn new(value: T) -> Self {
+            Self(std::sync::Mutex::new(value))
+        }
+        pub(crate) fn lock(&self) -> Acquired<MutexGuard<'_, T>> {
+            match self.0.lock() {
+                Ok(guard) => Acquired::Clean(guard),
+                Err(poisoned) => Acquired::Poisoned(poisoned.into_inner()),
+            }
+        }
+        pub(crate) fn try_lock(&self) -> Option<Acquired<MutexGuard<'_, T>>> {
+            match self.0.try_lock() {
+                Ok(guard) => Some(Acquired::Clean(guard)),
+                Err(TryLockError::Poisoned(poisoned)) => Some(Acquired::Poisoned(poisoned.into_inner())),
+                Err(TryLockError::WouldBlock) => None,
+            }
+        }
+        pub(crate) fn clear_poison(&self) {
+            self.0.clear_poison();
+        }
+        pub(crate) fn get_mut(&mut self) -> &mut T {
+            match self.0.get_mut() {
+                Ok(value) => value,
+                Err(poisoned) => poisoned.into_inner(),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "critical-section")]
+mod cs_mutex {
+    use core::cell::{Cell, UnsafeCell};
+    use core::marker::PhantomData;
+    use core::ops::{Deref, DerefMut};
+
+    use super::Acquired;
+
+    pub struct Mutex<T> {
+        locked: critical_section::Mutex<Cell<bool>>,
+        poisoned: critical_section::Mutex<Cell<bool>>,
+        value: UnsafeCell<T>,
+    }
+    // The value is accessed only by the owner of the lock.
+    unsafe impl<T: Send> Send for Mutex<T> {}
+    unsafe impl<T: Send> Sync for Mutex<T> {}
+
+    pub struct MutexGuard<'a, T> {
+        mutex: &'a Mutex<T>,
+        // Must be released by the thread which acquired the lock, as `std::sync::MutexGuard`.
+        _not_send: PhantomData<*const ()>,
+    }
+    unsafe impl<'a, T: Sync> Sync for MutexGuard<'a, T> {}
+
+    impl<T> Mutex<T> {
+        pub fn new(value: T) -> Self {
+            Self {
+                locked: critical_section::Mutex::new(Cell::new(false)),
+                poisoned: critical_section::Mutex::new(Cell::new(false)),
+                value: UnsafeCell::new(value),
+            }
+        }
+        pub(crate) fn lock(&self) -> Acquired<MutexGuard<'_, T>> {
+            loop {
+                if let Some(acquired) = self.try_lock() {
+                    return acquired;
+                }
+                #[cfg(feature = "std")]
+                std::thread::yield_now();
+                #[cfg(not(feature = "std"))]
+                core::hint::spin_loop();
+            }
+        }
+        pub(crate) fn try_lock(&self) -> Option<Acquired<MutexGuard<'_, T>>> {
+            critical_section::with(|cs| {
+                let locked = self.locked.borrow(cs);
+                if locked.get() {
+                    return None;
+                }
+                locked.set(true);
+                let guard = MutexGuard { mutex: self, _not_send: PhantomData };
+                if self.poisoned.borrow(cs).get() {
+                    Some(Acquired::Poisoned(guard))
+                } else {
+                    Some(Acquired::Clean(guard))
+                }
+            })
+        }
+        pub(crate) fn clear_poison(&self) {
+            critical_section::with(|cs| self.poisoned.borrow(cs).set(false));
+        }
+        pub(crate) fn get_mut(&mut self) -> &mut T {
+            self.value.get_mut()
+        }
+    }
+
+    impl<'a, T> Deref for MutexGuard<'a, T> {
+        type Target = T;
+        fn deref(&self) -> &T {
+            unsafe { &*self.mutex.value.get() }
+        }
+    }
+    impl<'a, T> DerefMut for MutexGuard<'a, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            unsafe { &mut *self.mutex.value.get() }
+        }
+    }
+    impl<'a, T> Drop for MutexGuard<'a, T> {
+        fn drop(&mut self) {
+            #[cfg(feature = "std")]
+            let panicking = std::thread::panicking();
+            #[cfg(not(feature = "std"))]
+            let panicking = false;
+            critical_section::with(|cs| {
+                if panicking {
+                    self.mutex.poisoned.borrow(cs).set(true);
+                }
+                self.mutex.locked.borrow(cs).set(false);
+            });
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::mpsc;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{LgfxError, SharedLgfxTarget};
+
+    /// Panics while holding the lock.
+    fn poison<T>(mutex: &Mutex<T>) {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let _acquired = mutex.lock();
+            panic!("poisoning the lock");
+        }));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn acquires_clean_lock() {
+        let mutex = Mutex::new(1);
+        assert!(matches!(mutex.lock(), Acquired::Clean(guard) if *guard == 1));
+        assert!(matches!(mutex.try_lock(), Some(Acquired::Clean(_))));
+    }
+
+    #[test]
+    fn try_lock_fails_while_locked() {
+        let mutex = Mutex::new(());
+        let acquired = mutex.lock();
+        assert!(mutex.try_lock().is_none());
+        drop(acquired);
+        assert!(matches!(mutex.try_lock(), Some(Acquired::Clean(_))));
+    }
+
+    #[test]
+    fn reports_poison_until_cleared() {
+        let mut mutex = Mutex::new(1);
+        poison(&mutex);
+        assert!(matches!(mutex.lock(), Acquired::Poisoned(guard) if *guard == 1));
+        assert!(matches!(mutex.try_lock(), Some(Acquired::Poisoned(_))));
+        assert_eq!(*mutex.get_mut(), 1);
+        mutex.clear_poison();
+        assert!(matches!(mutex.lock(), Acquired::Clean(_)));
+    }
+
+    #[test]
+    fn lock_timeout_times_out_while_locked() {
+        let mutex = Mutex::new(());
+        let _acquired = mutex.lock();
+        let start = Instant::now();
+        assert!(mutex.lock_timeout(Duration::from_millis(5)).is_none());
+        assert!(start.elapsed() >= Duration::from_millis(5));
+        assert!(mutex.lock_timeout(Duration::ZERO).is_none());
+    }
+
+    #[test]
+    fn lock_timeout_acquires_after_release() {
+        let mutex = Arc::new(Mutex::new(0));
+        let (locked_sender, locked) = mpsc::channel();
+        let holder = {
+            let mutex = mutex.clone();
+            std::thread::spawn(move || {
+                let Acquired::Clean(mut guard) = mutex.lock() else { panic!("poisoned") };
+                locked_sender.send(()).unwrap();
+                std::thread::sleep(Duration::from_millis(20));
+                *guard = 1;
+            })
+        };
+        locked.recv().unwrap();
+        assert!(matches!(mutex.lock_timeout(Duration::from_secs(10)), Some(Acquired::Clean(guard)) if *guard == 1));
+        holder.join().unwrap();
+    }
+
+    #[test]
+    fn fails_on_poison_by_default() {
+        let mutex = Mutex::new(core::ptr::null_mut());
+        poison(&mutex);
+        let shared = SharedLgfxTarget::new(&mutex);
+        assert!(matches!(shared.lock(), Err(LgfxError::Poisoned)));
+        assert!(matches!(shared.try_lock(), Err(LgfxError::Poisoned)));
+        mutex.clear_poison();
+        assert!(shared.lock().is_ok());
+    }
+
+    #[test]
+    fn recovers_from_poison() {
+        let mutex = Mutex::new(core::ptr::null_mut());
+        poison(&mutex);
+        assert!(SharedLgfxTarget::new(&mutex).poison_policy(PoisonPolicy::Recover).lock().is_ok());
+        // The poison is cleared for the others.
+        assert!(SharedLgfxTarget::new(&mutex).lock().is_ok());
+    }
+
+    #[test]
+    fn reports_contention() {
+        let mutex = Mutex::new(core::ptr::null_mut());
+        let shared = SharedLgfxTarget::new(&mutex);
+        let _guard = shared.lock().unwrap();
+        assert!(matches!(shared.try_lock(), Err(LgfxError::WouldBlock)));
+        assert!(matches!(shared.lock_timeout(Duration::from_millis(2)), Err(LgfxError::LockTimeout)));
+    }
+}
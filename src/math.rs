@@ -0,0 +1,30 @@
+//! Float functions which are not available in `core`.
+
+/// Converts the scale into the 16.16 fixed point number, rounding towards negative infinity.
+pub(crate) fn fixed16(scale: f32) -> i32 {
+    let scaled = scale * 65536.0;
+    let truncated = scaled as i32;
+    if (truncated as f32) > scaled {
+        truncated - 1
+    } else {
+        truncated
+    }
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn sqrt(value: f32) -> f32 {
+    value.sqrt()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn sqrt(value: f32) -> f32 {
+    if value <= 0.0 {
+        return 0.0;
+    }
+    // Newton's method from the estimation by halving the exponent.
+    let mut x = f32::from_bits((value.to_bits() >> 1) + 0x1fbd_1df5);
+    for _ in 0..4 {
+        x = 0.5 * (x + value / x);
+    }
+    x
+}
@@ -0,0 +1,148 @@
+use std::time::{Duration, Instant};
+
+use crate::event::Event;
+use crate::LgfxGuard;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdleState {
+    Active,
+    Dimmed,
+    Sleeping,
+}
+
+/// Lowers the backlight brightness after inactivity, and optionally puts the panel into sleep.
+///
+/// Report the user activity with `notify_activity` or `handle_event`,
+/// and call `update` periodically to apply the brightness.
+pub struct IdleDimmer {
+    active_brightness: u8,
+    dim_brightness: u8,
+    dim_timeout: Duration,
+    sleep_timeout: Option<Duration>,
+    last_activity: Instant,
+    state: IdleState,
+    // False until the first `update` applies the brightness of the state.
+    applied: bool,
+}
+
+impl IdleDimmer {
+    /// Creates a dimmer which regards `now` as the last activity.
+    /// The first `update` applies `active_brightness` even if the state does not change.
+    pub fn new(active_brightness: u8, dim_brightness: u8, dim_timeout: Duration, now: Instant) -> Self {
+        Self {
+            active_brightness,
+            dim_brightness,
+            dim_timeout,
+            sleep_timeout: None,
+            last_activity: now,
+            state: IdleState::Active,
+            applied: false,
+        }
+    }
+    /// Puts the panel into sleep after `sleep_timeout` of inactivity.
+    pub fn sleep_after(mut self, sleep_timeout: Duration) -> Self {
+        self.sleep_timeout = Some(sleep_timeout);
+        self
+    }
+    pub fn state(&self) -> IdleState {
+        self.state
+    }
+    pub fn notify_activity(&mut self, now: Instant) {
+        self.last_activity = now;
+    }
+    /// Regards the touch, key and button events as the user activity.
+    pub fn handle_event(&mut self, event: &Event, now: Instant) {
+        match event {
+            Event::KeyDown { .. }
+            | Event::Button(_)
+            | Event::TouchDown(_)
+            | Event::TouchMove(_) => self.notify_activity(now),
+            _ => {}
+        }
+    }
+
+    /// Updates the brightness and the sleep state of the panel and returns the new state.
+    pub fn update(&mut self, guard: &mut LgfxGuard, now: Instant) -> IdleState {
+        if let Some((previous, state)) = self.transition(now) {
+            if previous == IdleState::Sleeping && state != IdleState::Sleeping {
+                guard.wakeup();
+            }
+            match state {
+                IdleState::Active => guard.set_brightness(self.active_brightness),
+                IdleState::Dimmed => guard.set_brightness(self.dim_brightness),
+                IdleState::Sleeping => {
+                    guard.set_brightness(0);
+                    guard.sleep();
+                }
+            }
+        }
+        self.state
+    }
+
+    /// Moves to the state at `now`. Returns the previous and the new state if the new state must be applied.
+    fn transition(&mut self, now: Instant) -> Option<(IdleState, IdleState)> {
+        let idle = now.saturating_duration_since(self.last_activity);
+        let state = match self.sleep_timeout {
+            Some(sleep_timeout) if idle >= sleep_timeout => IdleState::Sleeping,
+            _ if idle >= self.dim_timeout => IdleState::Dimmed,
+            _ => IdleState::Active,
+        };
+        if self.applied && state == self.state {
+            return None;
+        }
+        let previous = core::mem::replace(&mut self.state, state);
+        self.applied = true;
+        Some((previous, state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn applies_active_brightness_first() {
+        let start = Instant::now();
+        let mut dimmer = IdleDimmer::new(200, 20, secs(10), start);
+        assert_eq!(dimmer.transition(start), Some((IdleState::Active, IdleState::Active)));
+        assert_eq!(dimmer.transition(start + secs(1)), None);
+    }
+
+    #[test]
+    fn dims_sleeps_and_wakes_up() {
+        let start = Instant::now();
+        let mut dimmer = IdleDimmer::new(200, 20, secs(10), start).sleep_after(secs(30));
+        dimmer.transition(start);
+        assert_eq!(dimmer.transition(start + secs(9)), None);
+        assert_eq!(dimmer.transition(start + secs(10)), Some((IdleState::Active, IdleState::Dimmed)));
+        assert_eq!(dimmer.transition(start + secs(29)), None);
+        assert_eq!(dimmer.transition(start + secs(30)), Some((IdleState::Dimmed, IdleState::Sleeping)));
+        assert_eq!(dimmer.state(), IdleState::Sleeping);
+
+        dimmer.handle_event(&Event::KeyDown { keycode: 0 }, start + secs(40));
+        assert_eq!(dimmer.transition(start + secs(40)), Some((IdleState::Sleeping, IdleState::Active)));
+        assert_eq!(dimmer.transition(start + secs(49)), None);
+    }
+
+    #[test]
+    fn ignores_events_without_user_activity() {
+        let start = Instant::now();
+        let mut dimmer = IdleDimmer::new(200, 20, secs(10), start);
+        dimmer.transition(start);
+        dimmer.handle_event(&Event::Resize { width: 1, height: 1 }, start + secs(5));
+        assert_eq!(dimmer.transition(start + secs(10)), Some((IdleState::Active, IdleState::Dimmed)));
+    }
+
+    #[test]
+    fn stays_dimmed_without_sleep_timeout() {
+        let start = Instant::now();
+        let mut dimmer = IdleDimmer::new(200, 20, secs(10), start);
+        dimmer.transition(start + secs(10));
+        assert_eq!(dimmer.state(), IdleState::Dimmed);
+        assert_eq!(dimmer.transition(start + secs(3600)), None);
+    }
+}
@@ -0,0 +1,318 @@
+//! Double-buffered frame presentation with DMA.
+
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+
+use crate::{
+    lgfx_c_create_sprite_with_depth, lgfx_c_end_write, lgfx_c_push_sprite_dma, lgfx_c_start_write,
+    lgfx_c_wait_dma, lgfx_target_t, Gfx, LgfxError, LgfxTarget, PoisonPolicy, Screen, Sprite,
+};
+
+/// Size of the buffers of `FramePresenter`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Two buffers of the full screen size.
+    Full,
+    /// Two buffers of `height` lines, for the panels whose full framebuffer does not fit in RAM.
+    /// The frame is rendered and transferred band by band from the top.
+    Bands { height: u32 },
+}
+
+/// Renders into one buffer while the other is transferred to the panel with DMA.
+///
+/// The buffers are RGB565 sprites in the DMA capable memory.
+/// The write transaction of a transfer is kept open after `present` returns, so the next band is rendered
+/// during the transfer, and is ended by the next `present` after the transfer finishes.
+/// The display can be locked by others between the presents, but the bus is not released for the other devices
+/// until `finish` or the drop.
+///
+/// ```ignore
+/// let mut presenter = FramePresenter::new(&gfx, PresentMode::Bands { height: 40 })?;
+/// loop {
+///     presenter.render_frame(|buffer, band| {
+///         // Draw in the band local coordinates.
+///         let mut display = LgfxDisplay::<_, Rgb565>::with_color(buffer);
+///         scene.draw(&mut display.translated(-band.top_left)).ok();
+///     })?;
+/// }
+/// ```
+pub struct FramePresenter<'a> {
+    gfx: &'a Gfx,
+    buffers: [Sprite; 2],
+    chain: SwapChain,
+    width: u32,
+    height: u32,
+    band_height: u32,
+}
+
+impl<'a> FramePresenter<'a> {
+    pub fn new(gfx: &'a Gfx, mode: PresentMode) -> Result<Self, LgfxError> {
+        let shared = gfx.as_shared();
+        let guard = shared.lock()?;
+        let (width, height) = guard.size();
+        let (width, height) = (width.max(0) as u32, height.max(0) as u32);
+        let band_height = band_height(mode, height);
+        let buffers = [
+            create_buffer(&guard, width, band_height)?,
+            create_buffer(&guard, width, band_height)?,
+        ];
+        Ok(Self {
+            gfx,
+            buffers,
+            chain: SwapChain::new(band_count(height, band_height)),
+            width,
+            height,
+            band_height,
+        })
+    }
+
+    /// Number of the bands in a frame. 1 in `PresentMode::Full`.
+    pub fn bands(&self) -> u32 {
+        self.chain.bands
+    }
+    /// Area of the screen which the back buffer is presented to.
+    pub fn band(&self) -> Rectangle {
+        band_area(self.width, self.height, self.band_height, self.chain.band)
+    }
+    /// Buffer to render the current band into. Its origin corresponds to the top left of `band()`.
+    pub fn back_buffer(&mut self) -> &mut Sprite {
+        &mut self.buffers[self.chain.back]
+    }
+
+    /// Starts transferring the back buffer to the current band, and swaps the buffers.
+    /// Waits for the previous transfer to finish before starting, and returns without waiting for this one.
+    pub fn present(&mut self) -> Result<(), LgfxError> {
+        let band = self.band();
+        let shared = self.gfx.as_shared();
+        let guard = shared.lock()?;
+        let mut ops = DisplayOps { target: guard.target(), buffers: &self.buffers };
+        self.chain.present(&mut ops, band.top_left.y);
+        Ok(())
+    }
+    /// Waits for the transfer and ends its write transaction to release the bus.
+    pub fn finish(&mut self) -> Result<(), LgfxError> {
+        let shared = self.gfx.as_shared();
+        let guard = shared.lock()?;
+        let mut ops = DisplayOps { target: guard.target(), buffers: &self.buffers };
+        self.chain.finish(&mut ops);
+        Ok(())
+    }
+
+    /// Renders and presents the bands until the end of the frame.
+    /// `draw` is called with the back buffer and the area of the screen for each band.
+    pub fn render_frame(&mut self, mut draw: impl FnMut(&mut Sprite, &Rectangle)) -> Result<(), LgfxError> {
+        loop {
+            let band = self.band();
+            draw(self.back_buffer(), &band);
+            self.present()?;
+            if self.chain.band == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl<'a> Drop for FramePresenter<'a> {
+    fn drop(&mut self) {
+        // The buffers must not be freed during the transfer even if other threads panicked.
+        let shared = self.gfx.as_shared().poison_policy(PoisonPolicy::Recover);
+        if let Ok(guard) = shared.lock() {
+            let mut ops = DisplayOps { target: guard.target(), buffers: &self.buffers };
+            self.chain.finish(&mut ops);
+        };
+    }
+}
+
+/// Operations on the display used to present the buffers.
+trait PresentOps {
+    fn start_write(&mut self);
+    fn end_write(&mut self);
+    fn wait_dma(&mut self);
+    /// Starts transferring the buffer to the line `y`.
+    fn push_dma(&mut self, buffer: usize, y: i32);
+}
+
+struct DisplayOps<'b> {
+    target: lgfx_target_t,
+    buffers: &'b [Sprite; 2],
+}
+
+impl<'b> PresentOps for DisplayOps<'b> {
+    fn start_write(&mut self) {
+        unsafe { lgfx_c_start_write(self.target) };
+    }
+    fn end_write(&mut self) {
+        unsafe { lgfx_c_end_write(self.target) };
+    }
+    fn wait_dma(&mut self) {
+        unsafe { lgfx_c_wait_dma(self.target) };
+    }
+    fn push_dma(&mut self, buffer: usize, y: i32) {
+        unsafe { lgfx_c_push_sprite_dma(self.buffers[buffer].target, self.target, 0, y) };
+    }
+}
+
+/// Swaps the buffers and keeps the write transaction of the transfer in flight.
+struct SwapChain {
+    back: usize,
+    band: u32,
+    bands: u32,
+    transferring: bool,
+}
+
+impl SwapChain {
+    fn new(bands: u32) -> Self {
+        Self { back: 0, band: 0, bands, transferring: false }
+    }
+    fn present(&mut self, ops: &mut impl PresentOps, y: i32) {
+        // The bus is busy until the previous transfer finishes.
+        self.finish(ops);
+        ops.start_write();
+        ops.push_dma(self.back, y);
+        self.transferring = true;
+        self.back ^= 1;
+        self.band = (self.band + 1) % self.bands;
+    }
+    fn finish(&mut self, ops: &mut impl PresentOps) {
+        if self.transferring {
+            ops.wait_dma();
+            ops.end_write();
+            self.transferring = false;
+        }
+    }
+}
+
+fn create_buffer(parent: &impl LgfxTarget, width: u32, height: u32) -> Result<Sprite, LgfxError> {
+    let target = unsafe { lgfx_c_create_sprite_with_depth(parent.target(), width as i32, height as i32, 16) };
+    if target.is_null() {
+        Err(LgfxError::OutOfMemory)
+    } else {
+        Ok(Sprite { target })
+    }
+}
+
+/// Height of the bands, clamped to the screen. At least 1 even for the empty screen.
+fn band_height(mode: PresentMode, height: u32) -> u32 {
+    match mode {
+        PresentMode::Full => height.max(1),
+        PresentMode::Bands { height: band_height } => band_height.clamp(1, height.max(1)),
+    }
+}
+
+fn band_count(height: u32, band_height: u32) -> u32 {
+    height.div_ceil(band_height).max(1)
+}
+
+/// Area of the `band`th band. The last band is shorter if the height is not a multiple of the band height.
+fn band_area(width: u32, height: u32, band_height: u32, band: u32) -> Rectangle {
+    let top = band * band_height;
+    Rectangle::new(
+        Point::new(0, top as i32),
+        Size::new(width, band_height.min(height.saturating_sub(top))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Op {
+        StartWrite,
+        EndWrite,
+        WaitDma,
+        PushDma { buffer: usize, y: i32 },
+    }
+
+    #[derive(Default)]
+    struct FakeOps {
+        ops: Vec<Op>,
+    }
+    impl PresentOps for FakeOps {
+        fn start_write(&mut self) {
+            self.ops.push(Op::StartWrite);
+        }
+        fn end_write(&mut self) {
+            self.ops.push(Op::EndWrite);
+        }
+        fn wait_dma(&mut self) {
+            self.ops.push(Op::WaitDma);
+        }
+        fn push_dma(&mut self, buffer: usize, y: i32) {
+            self.ops.push(Op::PushDma { buffer, y });
+        }
+    }
+
+    #[test]
+    fn swaps_buffers_and_wraps_bands() {
+        let mut chain = SwapChain::new(3);
+        let mut ops = FakeOps::default();
+        let mut presented = Vec::new();
+        for _ in 0..4 {
+            presented.push((chain.back, chain.band));
+            chain.present(&mut ops, 0);
+        }
+        assert_eq!(presented, [(0, 0), (1, 1), (0, 2), (1, 0)]);
+    }
+
+    #[test]
+    fn keeps_transfer_in_flight_until_next_present() {
+        let mut chain = SwapChain::new(2);
+        let mut ops = FakeOps::default();
+        chain.present(&mut ops, 0);
+        // Returns during the transfer to render the other buffer.
+        assert_eq!(ops.ops, [Op::StartWrite, Op::PushDma { buffer: 0, y: 0 }]);
+        ops.ops.clear();
+        chain.present(&mut ops, 40);
+        assert_eq!(
+            ops.ops,
+            [Op::WaitDma, Op::EndWrite, Op::StartWrite, Op::PushDma { buffer: 1, y: 40 }]
+        );
+    }
+
+    #[test]
+    fn finishes_transfer_once() {
+        let mut chain = SwapChain::new(1);
+        let mut ops = FakeOps::default();
+        chain.finish(&mut ops);
+        assert!(ops.ops.is_empty());
+        chain.present(&mut ops, 0);
+        ops.ops.clear();
+        chain.finish(&mut ops);
+        chain.finish(&mut ops);
+        assert_eq!(ops.ops, [Op::WaitDma, Op::EndWrite]);
+    }
+
+    #[test]
+    fn shortens_last_band() {
+        let band_height = band_height(PresentMode::Bands { height: 40 }, 100);
+        assert_eq!(band_count(100, band_height), 3);
+        assert_eq!(band_area(320, 100, band_height, 1), Rectangle::new(Point::new(0, 40), Size::new(320, 40)));
+        assert_eq!(band_area(320, 100, band_height, 2), Rectangle::new(Point::new(0, 80), Size::new(320, 20)));
+    }
+
+    #[test]
+    fn presents_full_screen_at_once() {
+        let band_height = band_height(PresentMode::Full, 240);
+        assert_eq!(band_count(240, band_height), 1);
+        assert_eq!(band_area(320, 240, band_height, 0), Rectangle::new(Point::zero(), Size::new(320, 240)));
+    }
+
+    #[test]
+    fn clamps_band_taller_than_screen() {
+        let band_height = band_height(PresentMode::Bands { height: 500 }, 240);
+        assert_eq!(band_height, 240);
+        assert_eq!(band_count(240, band_height), 1);
+        assert_eq!(band_area(320, 240, band_height, 0), Rectangle::new(Point::zero(), Size::new(320, 240)));
+    }
+
+    #[test]
+    fn handles_empty_screen() {
+        for mode in [PresentMode::Full, PresentMode::Bands { height: 0 }, PresentMode::Bands { height: 40 }] {
+            let band_height = band_height(mode, 0);
+            assert_eq!(band_height, 1);
+            assert_eq!(band_count(0, band_height), 1);
+            assert_eq!(band_area(320, 0, band_height, 0), Rectangle::new(Point::zero(), Size::new(320, 0)));
+        }
+    }
+}
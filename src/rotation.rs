@@ -0,0 +1,180 @@
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+
+/// Rotation of the display.
+///
+/// `DegN` rotates the logical coordinates by N degrees clockwise against the physical panel.
+/// `MirroredDegN` mirrors the logical Y axis in addition to the rotation,
+/// e.g. `MirroredDeg0` is upside down and `MirroredDeg180` is mirrored horizontally.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    Deg0 = 0,
+    Deg90 = 1,
+    Deg180 = 2,
+    Deg270 = 3,
+    MirroredDeg0 = 4,
+    MirroredDeg90 = 5,
+    MirroredDeg180 = 6,
+    MirroredDeg270 = 7,
+}
+
+impl TryFrom<u8> for Rotation {
+    type Error = ();
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Deg0),
+            1 => Ok(Self::Deg90),
+            2 => Ok(Self::Deg180),
+            3 => Ok(Self::Deg270),
+            4 => Ok(Self::MirroredDeg0),
+            5 => Ok(Self::MirroredDeg90),
+            6 => Ok(Self::MirroredDeg180),
+            7 => Ok(Self::MirroredDeg270),
+            _ => Err(()),
+        }
+    }
+}
+impl From<Rotation> for u8 {
+    fn from(value: Rotation) -> Self {
+        value as u8
+    }
+}
+
+impl Rotation {
+    /// Number of 90 degree steps.
+    fn quarter_turns(self) -> u8 {
+        (self as u8) & 3
+    }
+    pub fn is_mirrored(self) -> bool {
+        (self as u8) & 4 != 0
+    }
+    /// Returns true if the logical width corresponds to the physical height.
+    pub fn swaps_axes(self) -> bool {
+        self.quarter_turns() & 1 != 0
+    }
+    /// Returns the logical size of the panel which has `physical` size.
+    pub fn logical_size(self, physical: Size) -> Size {
+        if self.swaps_axes() {
+            Size::new(physical.height, physical.width)
+        } else {
+            physical
+        }
+    }
+    /// Returns the physical size of the panel which has `logical` size in this rotation.
+    pub fn physical_size(self, logical: Size) -> Size {
+        // Swapping axes is its own inverse.
+        self.logical_size(logical)
+    }
+
+    /// Transforms the point in the physical panel coordinates into the logical coordinates.
+    pub fn to_logical(self, point: Point, physical: Size) -> Point {
+        let (w, h) = (physical.width as i32, physical.height as i32);
+        let logical = match self.quarter_turns() {
+            0 => point,
+            1 => Point::new(point.y, w - 1 - point.x),
+            2 => Point::new(w - 1 - point.x, h - 1 - point.y),
+            _ => Point::new(h - 1 - point.y, point.x),
+        };
+        if self.is_mirrored() {
+            let logical_height = self.logical_size(physical).height as i32;
+            Point::new(logical.x, logical_height - 1 - logical.y)
+        } else {
+            logical
+        }
+    }
+    /// Transforms the point in the logical coordinates into the physical panel coordinates.
+    pub fn to_physical(self, point: Point, physical: Size) -> Point {
+        let (w, h) = (physical.width as i32, physical.height as i32);
+        let point = if self.is_mirrored() {
+            let logical_height = self.logical_size(physical).height as i32;
+            Point::new(point.x, logical_height - 1 - point.y)
+        } else {
+            point
+        };
+        match self.quarter_turns() {
+            0 => point,
+            1 => Point::new(w - 1 - point.y, point.x),
+            2 => Point::new(w - 1 - point.x, h - 1 - point.y),
+            _ => Point::new(point.y, h - 1 - point.x),
+        }
+    }
+    /// Transforms the rectangle in the physical panel coordinates into the logical coordinates.
+    pub fn rect_to_logical(self, rect: &Rectangle, physical: Size) -> Rectangle {
+        match rect.bottom_right() {
+            Some(bottom_right) => Rectangle::with_corners(
+                self.to_logical(rect.top_left, physical),
+                self.to_logical(bottom_right, physical),
+            ),
+            None => Rectangle::new(self.to_logical(rect.top_left, physical), Size::zero()),
+        }
+    }
+    /// Transforms the rectangle in the logical coordinates into the physical panel coordinates.
+    pub fn rect_to_physical(self, rect: &Rectangle, physical: Size) -> Rectangle {
+        match rect.bottom_right() {
+            Some(bottom_right) => Rectangle::with_corners(
+                self.to_physical(rect.top_left, physical),
+                self.to_physical(bottom_right, physical),
+            ),
+            None => Rectangle::new(self.to_physical(rect.top_left, physical), Size::zero()),
+        }
+    }
+    /// Transforms the point in the logical coordinates of `self` into the logical coordinates of `to`,
+    /// e.g. to keep cached layouts or touch points after the rotation is changed.
+    pub fn convert(self, point: Point, to: Rotation, physical: Size) -> Point {
+        to.to_logical(self.to_physical(point, physical), physical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::prelude::PointsIter;
+
+    use super::*;
+
+    const ROTATIONS: [Rotation; 8] = [
+        Rotation::Deg0,
+        Rotation::Deg90,
+        Rotation::Deg180,
+        Rotation::Deg270,
+        Rotation::MirroredDeg0,
+        Rotation::MirroredDeg90,
+        Rotation::MirroredDeg180,
+        Rotation::MirroredDeg270,
+    ];
+    const PHYSICAL: Size = Size::new(4, 3);
+
+    #[test]
+    fn to_physical_inverts_to_logical() {
+        for rotation in ROTATIONS {
+            for point in Rectangle::new(Point::zero(), PHYSICAL).points() {
+                let logical = rotation.to_logical(point, PHYSICAL);
+                assert!(
+                    Rectangle::new(Point::zero(), rotation.logical_size(PHYSICAL)).contains(logical),
+                    "{:?} maps {:?} out of the screen",
+                    rotation,
+                    point
+                );
+                assert_eq!(rotation.to_physical(logical, PHYSICAL), point, "{:?}", rotation);
+            }
+        }
+    }
+
+    #[test]
+    fn maps_physical_origin() {
+        let expected = [
+            (Rotation::Deg0, Point::new(0, 0)),
+            (Rotation::Deg90, Point::new(0, 3)),
+            (Rotation::Deg180, Point::new(3, 2)),
+            (Rotation::Deg270, Point::new(2, 0)),
+            // Upside down.
+            (Rotation::MirroredDeg0, Point::new(0, 2)),
+            (Rotation::MirroredDeg90, Point::new(0, 0)),
+            // Mirrored horizontally.
+            (Rotation::MirroredDeg180, Point::new(3, 0)),
+            (Rotation::MirroredDeg270, Point::new(2, 3)),
+        ];
+        for (rotation, logical) in expected {
+            assert_eq!(rotation.to_logical(Point::zero(), PHYSICAL), logical, "{:?}", rotation);
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use alloc::vec;
+use embedded_graphics::{
+    prelude::*,
+    primitives::Rectangle,
+    text::{
+        renderer::{CharacterStyle, TextMetrics, TextRenderer},
+        Baseline,
+    },
+};
+
+use crate::{lgfx_c_create_sprite_with_depth, lgfx_c_sprite_read_values_u8};
+use crate::{
+    ColorRgb332, DrawChars, DrawPrimitives, FontManupulation, LgfxDisplay, LgfxFont, LgfxPixelColor,
+    LgfxTarget, Sprite,
+};
+
+/// Character style to draw embedded-graphics `Text` with LovyanGFX fonts.
+///
+/// Glyphs are rendered by LovyanGFX into a temporary 8bpp sprite, and then written to the draw target.
+/// Thus the style can be used with any `DrawTarget`, not only with `LgfxDisplay`.
+/// Only the part inside the target is rendered, band by band if the memory is short.
+/// If not even a line can be rendered, only the background is drawn.
+///
+/// `Text::draw` cannot detect the target type, so it takes the sprite path for `LgfxDisplay` too.
+/// `LgfxDisplay::draw_text` draws with `drawChar` of LovyanGFX directly into the display.
+#[derive(Clone, Copy, Debug)]
+pub struct LgfxCharacterStyle<C> {
+    pub font: LgfxFont,
+    pub text_color: Option<C>,
+    pub background_color: Option<C>,
+    pub scale_x: f32,
+    pub scale_y: f32,
+}
+
+impl<C> LgfxCharacterStyle<C> {
+    pub fn new(font: LgfxFont, text_color: C) -> Self {
+        Self {
+            font,
+            text_color: Some(text_color),
+            background_color: None,
+            scale_x: 1.0,
+            scale_y: 1.0,
+        }
+    }
+    pub fn background_color(mut self, background_color: C) -> Self {
+        self.background_color = Some(background_color);
+        self
+    }
+    pub fn scale(mut self, scale_x: f32, scale_y: f32) -> Self {
+        self.scale_x = scale_x;
+        self.scale_y = scale_y;
+        self
+    }
+
+    fn scaled(&self, value: i16, scale: f32) -> i32 {
+        ((value as i32) * crate::math::fixed16(scale)) >> 16
+    }
+    /// Returns the Y coordinate of the top of the line.
+    fn line_top(&self, position: Point, baseline: Baseline) -> i32 {
+        let metrics = self.font.default_metrics();
+        let height = self.scaled(metrics.height, self.scale_y);
+        match baseline {
+            Baseline::Top => position.y,
+            Baseline::Middle => position.y - (height - 1) / 2,
+            Baseline::Bottom => position.y - (height - 1),
+            Baseline::Alphabetic => position.y - self.scaled(metrics.baseline, self.scale_y),
+        }
+    }
+    fn line_size(&self, width: i32) -> Size {
+        let height = self.scaled(self.font.default_metrics().height, self.scale_y);
+        Size::new(width.max(0) as u32, height.max(0) as u32)
+    }
+
+    /// Renders the glyphs of the text whose box is `area` into the `visible` part of the target,
+    /// through the temporary sprite. Returns false if the sprite cannot be prepared.
+    fn render_glyphs<D>(
+        &self,
+        text: &str,
+        area: &Rectangle,
+        visible: &Rectangle,
+        lead: i32,
+        text_color: C,
+        target: &mut D,
+    ) -> Result<bool, D::Error>
+    where
+        C: PixelColor,
+        D: DrawTarget<Color = C>,
+    {
+        // Halve the height of the sprite until it can be allocated.
+        let mut band_height = visible.size.height;
+        let mut sprite = loop {
+            let sprite = unsafe {
+                lgfx_c_create_sprite_with_depth(core::ptr::null_mut(), visible.size.width as i32, band_height as i32, 8)
+            };
+            if !sprite.is_null() {
+                break Sprite { target: sprite };
+            }
+            if band_height == 1 {
+                return Ok(false);
+            }
+            band_height = band_height.div_ceil(2);
+        };
+        if sprite.set_font(self.font).is_err() {
+            return Ok(false);
+        }
+        let y_offset = self.scaled(self.font.default_metrics().y_offset, self.scale_y);
+        let mut line = vec![0u8; visible.size.width as usize];
+        let mut top = 0;
+        while top < visible.size.height {
+            let band = Rectangle::new(
+                visible.top_left + Point::new(0, top as i32),
+                Size::new(visible.size.width, band_height.min(visible.size.height - top)),
+            );
+            // Non-zero pixel values are the glyph pixels.
+            let offset = band.top_left - area.top_left;
+            sprite.clear(ColorRgb332::new(0));
+            sprite.draw_chars(
+                text,
+                lead - offset.x,
+                -y_offset - offset.y,
+                ColorRgb332::new(0xff),
+                None,
+                self.scale_x,
+                self.scale_y,
+            );
+            for y in 0..band.size.height as i32 {
+                unsafe {
+                    lgfx_c_sprite_read_values_u8(sprite.target, 0, y, band.size.width as i32, 1, line.as_mut_ptr());
+                }
+                let row = Rectangle::new(band.top_left + Point::new(0, y), Size::new(band.size.width, 1));
+                match self.background_color {
+                    Some(background_color) => target.fill_contiguous(
+                        &row,
+                        line.iter().map(|&value| if value != 0 { text_color } else { background_color }),
+                    )?,
+                    None => target.draw_iter(
+                        row.points()
+                            .zip(line.iter())
+                            .filter(|(_, &value)| value != 0)
+                            .map(|(point, _)| Pixel(point, text_color)),
+                    )?,
+                }
+            }
+            top += band.size.height;
+        }
+        Ok(true)
+    }
+}
+
+impl<C: PixelColor> CharacterStyle for LgfxCharacterStyle<C> {
+    type Color = C;
+
+    fn set_text_color(&mut self, text_color: Option<Self::Color>) {
+        self.text_color = text_color;
+    }
+    fn set_background_color(&mut self, background_color: Option<Self::Color>) {
+        self.background_color = background_color;
+    }
+}
+
+impl<C: PixelColor> TextRenderer for LgfxCharacterStyle<C> {
+    type Color = C;
+
+    fn draw_string<D>(
+        &self,
+        text: &str,
+        position: Point,
+        baseline: Baseline,
+        target: &mut D,
+    ) -> Result<Point, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        let extent = self.font.string_extent(text, self.scale_x, self.scale_y);
+        let next_position = position + Size::new(extent.advance.max(0) as u32, 0);
+        let area = Rectangle::new(
+            Point::new(position.x - extent.lead, self.line_top(position, baseline)),
+            self.line_size(extent.width),
+        );
+        if area.is_zero_sized() {
+            return Ok(next_position);
+        }
+
+        let text_color = match self.text_color {
+            Some(text_color) => text_color,
+            None => {
+                if let Some(background_color) = self.background_color {
+                    target.fill_solid(&area, background_color)?;
+                }
+                return Ok(next_position);
+            }
+        };
+
+        // Render only the visible part.
+        let visible = area.intersection(&target.bounding_box());
+        if visible.is_zero_sized() {
+            return Ok(next_position);
+        }
+        if !self.render_glyphs(text, &area, &visible, extent.lead, text_color, target)? {
+            if let Some(background_color) = self.background_color {
+                target.fill_solid(&visible, background_color)?;
+            }
+        }
+        Ok(next_position)
+    }
+
+    fn draw_whitespace<D>(
+        &self,
+        width: u32,
+        position: Point,
+        baseline: Baseline,
+        target: &mut D,
+    ) -> Result<Point, D::Error>
+    where
+        D: DrawTarget<Color = Self::Color>,
+    {
+        if let Some(background_color) = self.background_color {
+            let area = Rectangle::new(
+                Point::new(position.x, self.line_top(position, baseline)),
+                self.line_size(width as i32),
+            );
+            target.fill_solid(&area, background_color)?;
+        }
+        Ok(position + Size::new(width, 0))
+    }
+
+    fn measure_string(&self, text: &str, position: Point, baseline: Baseline) -> TextMetrics {
+        let extent = self.font.string_extent(text, self.scale_x, self.scale_y);
+        TextMetrics {
+            bounding_box: Rectangle::new(
+                Point::new(position.x - extent.lead, self.line_top(position, baseline)),
+                self.line_size(extent.width),
+            ),
+            next_position: position + Size::new(extent.advance.max(0) as u32, 0),
+        }
+    }
+
+    fn line_height(&self) -> u32 {
+        self.scaled(self.font.default_metrics().y_advance, self.scale_y).max(0) as u32
+    }
+}
+
+impl<'a, Target: LgfxTarget, C> LgfxDisplay<'a, Target, C> {
+    /// Draws the text with `drawChar` of LovyanGFX directly into the display, without the temporary sprite.
+    /// The colors are drawn with the native color path of `C`, e.g. RGB565 for `Rgb565`.
+    /// Returns the position of the next character as `Text::draw`.
+    pub fn draw_text(&mut self, text: &str, position: Point, style: &LgfxCharacterStyle<C>, baseline: Baseline) -> Point
+    where
+        C: LgfxPixelColor,
+        Self: DrawChars<C::Native> + DrawPrimitives<C::Native>,
+    {
+        let to_native = |color: C| color.to_native();
+        let extent = style.font.string_extent(text, style.scale_x, style.scale_y);
+        let next_position = position + Size::new(extent.advance.max(0) as u32, 0);
+        let top = style.line_top(position, baseline);
+        let area = Rectangle::new(Point::new(position.x - extent.lead, top), style.line_size(extent.width));
+
+        match style.text_color {
+            Some(text_color) => {
+                let font = self.get_font();
+                if self.set_font(style.font).is_err() {
+                    return next_position;
+                }
+                let y_offset = style.scaled(style.font.default_metrics().y_offset, style.scale_y);
+                DrawChars::<C::Native>::draw_chars(
+                    self,
+                    text,
+                    position.x,
+                    top - y_offset,
+                    to_native(text_color),
+                    style.background_color.map(to_native),
+                    style.scale_x,
+                    style.scale_y,
+                );
+                if let Ok(font) = font {
+                    self.set_font(font).ok();
+                }
+            }
+            None => match style.background_color {
+                Some(background_color) => self.fill_rect(
+                    area.top_left.x,
+                    area.top_left.y,
+                    area.size.width as i32,
+                    area.size.height as i32,
+                    to_native(background_color),
+                ),
+                None => return next_position,
+            },
+        }
+        self.mark_dirty(&area);
+        next_position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embedded_graphics::pixelcolor::Rgb888;
+
+    use super::*;
+    use crate::fonts;
+
+    fn style() -> LgfxCharacterStyle<Rgb888> {
+        LgfxCharacterStyle::new(fonts::Font0, Rgb888::WHITE)
+    }
+
+    #[test]
+    fn measures_advance_of_glyphs() {
+        let text = "Hello";
+        let advance: i32 = text.chars().map(|c| fonts::Font0.metrics(c).unwrap().x_advance as i32).sum();
+        let metrics = style().measure_string(text, Point::new(10, 20), Baseline::Top);
+        assert_eq!(metrics.next_position, Point::new(10 + advance, 20));
+        assert_eq!(metrics.bounding_box.top_left.y, 20);
+        assert_eq!(metrics.bounding_box.size.height, fonts::Font0.default_metrics().height as u32);
+    }
+
+    #[test]
+    fn measures_scaled_string() {
+        let text = "Hi";
+        let metrics = style().measure_string(text, Point::zero(), Baseline::Top);
+        let scaled = style().scale(2.0, 3.0).measure_string(text, Point::zero(), Baseline::Top);
+        assert_eq!(scaled.next_position.x, metrics.next_position.x * 2);
+        assert_eq!(scaled.bounding_box.size.height, metrics.bounding_box.size.height * 3);
+    }
+
+    #[test]
+    fn aligns_alphabetic_baseline() {
+        let baseline = fonts::Font0.default_metrics().baseline as i32;
+        let metrics = style().measure_string("g", Point::new(0, 30), Baseline::Alphabetic);
+        assert_eq!(metrics.bounding_box.top_left.y, 30 - baseline);
+    }
+
+    #[test]
+    fn line_height_is_y_advance() {
+        let y_advance = fonts::Font0.default_metrics().y_advance as u32;
+        assert_eq!(style().line_height(), y_advance);
+        assert_eq!(style().scale(1.0, 2.0).line_height(), y_advance * 2);
+    }
+}
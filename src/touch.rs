@@ -0,0 +1,112 @@
+use crate::{lgfx_c_calibrate_touch, lgfx_c_get_touch, lgfx_c_has_touch, lgfx_c_set_touch_calibrate};
+use crate::{touch_point_t, Color, LgfxGuard, LgfxTarget};
+
+/// Maximum number of touch points which can be read at once.
+pub const MAX_TOUCH_POINTS: usize = 5;
+
+/// A touch point in the rotated screen coordinates.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TouchPoint {
+    pub x: i32,
+    pub y: i32,
+    /// Size or pressure of the touch. 0 if the touch controller does not report it.
+    pub size: u16,
+    /// ID of the touch point to track the same finger across multiple reads.
+    pub id: u16,
+}
+
+impl From<touch_point_t> for TouchPoint {
+    fn from(value: touch_point_t) -> Self {
+        Self {
+            x: value.x as i32,
+            y: value.y as i32,
+            size: value.size,
+            id: value.id,
+        }
+    }
+}
+
+/// Touch calibration parameters of LovyanGFX.
+/// Store the bytes returned by `to_bytes` in the non-volatile storage and restore them with `from_bytes`
+/// to skip the calibration at the next boot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TouchCalibration {
+    pub parameters: [u16; 8],
+}
+
+impl TouchCalibration {
+    pub const SIZE: usize = 16;
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        for (chunk, parameter) in bytes.chunks_exact_mut(2).zip(self.parameters.iter()) {
+            chunk.copy_from_slice(&parameter.to_le_bytes());
+        }
+        bytes
+    }
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Self {
+        let mut parameters = [0u16; 8];
+        for (parameter, chunk) in parameters.iter_mut().zip(bytes.chunks_exact(2)) {
+            *parameter = u16::from_le_bytes([chunk[0], chunk[1]]);
+        }
+        Self { parameters }
+    }
+}
+
+impl<'a> LgfxGuard<'a> {
+    /// Returns true if the display has a touch controller.
+    /// On Linux, the mouse of the SDL window is reported as a touch.
+    pub fn has_touch(&mut self) -> bool {
+        unsafe { lgfx_c_has_touch(self.target()) }
+    }
+    /// Reads the first touch point. Returns `None` if not touched.
+    pub fn get_touch(&mut self) -> Option<TouchPoint> {
+        let mut points = [TouchPoint::default()];
+        self.get_touches(&mut points).first().copied()
+    }
+    /// Reads up to `points.len()` touch points and returns the touched points.
+    pub fn get_touches<'p>(&mut self, points: &'p mut [TouchPoint]) -> &'p [TouchPoint] {
+        let mut raw_points = [touch_point_t { x: 0, y: 0, size: 0, id: 0 }; MAX_TOUCH_POINTS];
+        let count = points.len().min(MAX_TOUCH_POINTS);
+        let count = unsafe { lgfx_c_get_touch(self.target(), raw_points.as_mut_ptr(), count as u8) } as usize;
+        let count = count.min(points.len());
+        for (point, raw_point) in points.iter_mut().zip(raw_points.iter()).take(count) {
+            *point = (*raw_point).into();
+        }
+        &points[..count]
+    }
+    /// Runs the interactive touch calibration, which asks the user to touch the corners of the screen.
+    pub fn calibrate_touch<C: Color>(&mut self, fg: C, bg: C, size: u8) -> TouchCalibration {
+        let mut calibration = TouchCalibration::default();
+        unsafe {
+            lgfx_c_calibrate_touch(
+                self.target(),
+                calibration.parameters.as_mut_ptr(),
+                fg.as_u32(),
+                bg.as_u32(),
+                size,
+            );
+        }
+        calibration
+    }
+    /// Applies the calibration parameters obtained by `calibrate_touch` before.
+    pub fn set_touch_calibration(&mut self, calibration: &TouchCalibration) {
+        let mut parameters = calibration.parameters;
+        unsafe {
+            lgfx_c_set_touch_calibrate(self.target(), parameters.as_mut_ptr());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calibration_round_trips_through_bytes() {
+        let calibration = TouchCalibration { parameters: [0, 1, 0x00ff, 0x0100, 0x1234, 0x8000, 0xfffe, u16::MAX] };
+        let bytes = calibration.to_bytes();
+        assert_eq!(bytes[4..8], [0xff, 0x00, 0x00, 0x01]);
+        assert_eq!(TouchCalibration::from_bytes(&bytes), calibration);
+    }
+}